@@ -1,39 +1,91 @@
 use std::io;
+use std::env;
+use std::time::Duration;
 
 extern crate pushfour;
 extern crate rand;
 
-use pushfour::minimax::{Minimax, Game};
+use pushfour::minimax::{Minimax, Game, SearchOptions};
+use pushfour::ponder::Ponderer;
 use pushfour::PushfourGame;
 use pushfour::board::*;
+use pushfour::record::{GameRecord, GameResult};
 use pushfour::util::*;
 use rand::Rng;
 
 static DEPTH: i32 = 7;
-static NUM_ROCKS: u32 = 4;
+// Plies the bot keeps searching wins and blocks for past its depth
+static EXTENSION: i32 = 4;
+// Positions with fewer reachable empty cells than this are searched to the end of the game
+static ENDGAME_CELLS: u32 = 14;
+static NUM_ROCKS: usize = 4;
+
+// Reads `-t MILLIS` (the bot's time budget), `-r SEED` (the seed for the rocks) and `-p` (ponder
+// on the human's time) from the command line
+fn parse_args() -> (Option<Duration>, Option<u64>, bool) {
+    let args: Vec<String> = env::args().collect();
+    let (mut budget, mut seed, mut ponder) = (None, None, false);
+    let mut i = 1;
+    while i < args.len() {
+        match &*args[i] {
+            "-p" => {
+                ponder = true;
+                i += 1;
+                continue;
+            }
+            "-t" if i + 1 < args.len() =>
+                budget = args[i + 1].parse::<u64>().ok().map(Duration::from_millis),
+            "-r" if i + 1 < args.len() => seed = args[i + 1].parse::<u64>().ok(),
+            _ => {}
+        }
+        i += 2;
+    }
+    (budget, seed, ponder)
+}
 
-// Clean up player... I don't think Board needs it
 fn main() {
-    let g = PushfourGame::new(Player::Red);
+    let g = PushfourGame::new();
     let mut b = Board::new(BOARD_SIZE);
-    println!("New pushfour game. Difficulty: {}", DEPTH);
-    let mut rng = rand::thread_rng();
-    let mut rcnt = 0;
-    loop {
-        let x = (rng.gen::<usize>() % BOARD_SIZE) as usize;
-        let y = (rng.gen::<usize>() % BOARD_SIZE) as usize;
-        if b.get(x, y).is_none() {
-            b.set(x, y, Some(Piece::Rock));
-            rcnt += 1;
-            if rcnt == NUM_ROCKS { break; }
-        }
+    let (budget, seed, mut ponder) = parse_args();
+    let mut engine = Minimax::new();
+    engine.set_options(SearchOptions {
+        extension: EXTENSION,
+        endgame: ENDGAME_CELLS,
+        ..SearchOptions::default()
+    });
+    let mut bot = Ponderer::new(engine);
+    // The reply the bot expects, if it's pondering it
+    let mut predicted: Option<Move> = None;
+    match budget {
+        Some(t) => println!("New pushfour game. Time per move: {:?}", t),
+        None => println!("New pushfour game. Difficulty: {}", DEPTH),
     }
+    let seed = seed.unwrap_or_else(|| rand::thread_rng().gen::<u64>());
+    let rock_opts = RockOptions { count: NUM_ROCKS, ..Default::default() };
+    b.place_rocks(seed, &rock_opts).expect("couldn't place rocks");
+    println!("Rock seed: {}", seed);
+
+    println!("Pondering {} (type 'ponder' to toggle)", if ponder { "on" } else { "off" });
 
     println!("Board state: {:?}", b);
 
+    let mut record = GameRecord::new(b.clone());
+    record.blue = "human".to_string();
+    record.red = "pushfour-rust".to_string();
+    match budget {
+        Some(t) => {
+            let millis = t.as_secs() * 1000 + (t.subsec_nanos() / 1_000_000) as u64;
+            record.set_tag("Time", &millis.to_string());
+        }
+        None => record.set_tag("Depth", &DEPTH.to_string()),
+    }
+    record.set_tag("Seed", &seed.to_string());
+    record.set_tag("Started", &GameRecord::timestamp());
+
     loop {
         if b.get_moves_set().len() == 0 {
             println!("\nCat's game.\n");
+            record.result = GameResult::Draw;
             break;
         }
 
@@ -42,6 +94,12 @@ fn main() {
         io::stdin().read_line(&mut human_input)
             .ok()
             .expect("Failed reading input");
+        if human_input.trim() == "ponder" {
+            ponder = !ponder;
+            if !ponder { bot.engine(); }
+            println!("Pondering {}", if ponder { "on" } else { "off" });
+            continue;
+        }
         let coords: Vec<Option<usize>> = human_input.trim()
             .split(":")
             .map(|c| c.parse::<usize>().ok())
@@ -58,24 +116,55 @@ fn main() {
 
         // Apply human move
         b = g.apply(&b, human_move);
+        record.moves.push(human_move);
         println!("Board state: {:?}", b);
         if b.is_win_state(Player::Blue) {
             println!("\nYou win!\n");
+            record.result = GameResult::BlueWins;
             break;
         }
 
         if b.get_moves_set().len() == 0 {
             println!("\nCat's game.\n");
+            record.result = GameResult::Draw;
             break;
         }
 
-        // Compute and apply bot move
-        let bot_move = Minimax::best_move(DEPTH, &g, &b);
+        // Compute and apply bot move, stopping any pondering first
+        if bot.is_pondering() && predicted == Some(human_move) { println!("Ponder hit"); }
+        let result = match budget {
+            Some(t) => {
+                let result = bot.engine().search_timed(t, &g, &b);
+                println!("Searched to depth {}", result.depth);
+                result
+            }
+            None => bot.engine().search(DEPTH, &g, &b),
+        };
+        if let Some(p) = result.proven() { println!("Proven {}", p); }
+        let bot_move = result.best_move;
         b = g.apply(&b, bot_move);
+        record.moves.push(bot_move);
         println!("New state: {:?}", b);
         if b.is_win_state(Player::Red) {
             println!("\nI win!\n");
+            record.result = GameResult::RedWins;
             break;
         }
+
+        // Search the reply the bot expects while the human thinks, or all replies if it doesn't
+        // expect one
+        if ponder {
+            predicted = match result.pv.get(1) {
+                Some(m) if b.get_moves_set().contains(m) => Some(*m),
+                _ => None,
+            };
+            match predicted {
+                Some(m) => bot.start(&g, &g.apply(&b, m)),
+                None => bot.start(&g, &b),
+            }
+        }
     }
+
+    record.set_tag("Finished", &GameRecord::timestamp());
+    println!("{}", record);
 }
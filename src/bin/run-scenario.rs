@@ -2,30 +2,51 @@ extern crate core;
 extern crate pushfour;
 extern crate regex;
 
-use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 use std::env;
 use std::io;
 use std::process;
+use std::str::FromStr;
+use std::time::Duration;
 use regex::Regex;
 use core::num;
 
 use pushfour::util::*;
-use pushfour::board::Board;
+use pushfour::board::{Board, BoardParseError, NotationError};
 use pushfour::PushfourGame;
-use pushfour::minimax::{Minimax, Game};
+use pushfour::minimax::{Minimax, Game, SearchOptions, SearchResult};
+use pushfour::mcts::{Mcts, MctsOptions, MctsResult};
 
 #[derive(Debug)]
 pub enum CliError {
     Io(io::Error),
     Regex(regex::Error),
     Parse(num::ParseIntError),
+    Board(BoardParseError),
+    Notation(NotationError),
     InvalidName,
+    NoDepth,
     NotFound,
 }
 
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Io(ref e) => write!(f, "{}", e),
+            CliError::Regex(ref e) => write!(f, "{}", e),
+            CliError::Parse(ref e) => write!(f, "bad depth: {}", e),
+            CliError::Board(ref e) => write!(f, "bad board: {}", e),
+            CliError::Notation(ref e) => write!(f, "bad position: {}", e),
+            CliError::InvalidName => write!(f, "no depth_N in file name"),
+            CliError::NoDepth => write!(f, "no depth given"),
+            CliError::NotFound => write!(f, "not found"),
+        }
+    }
+}
+
 impl From<io::Error> for CliError {
     fn from(err: io::Error) -> CliError {
         CliError::Io(err)
@@ -44,6 +65,18 @@ impl From<num::ParseIntError> for CliError {
     }
 }
 
+impl From<BoardParseError> for CliError {
+    fn from(err: BoardParseError) -> CliError {
+        CliError::Board(err)
+    }
+}
+
+impl From<NotationError> for CliError {
+    fn from(err: NotationError) -> CliError {
+        CliError::Notation(err)
+    }
+}
+
 fn parse_scenario_path(path: &str) -> Result<i32, CliError> {
     let re = Regex::new(r"depth_(\d*)\.txt$").unwrap();
     let m = try!(re.captures_iter(path).nth(0).ok_or(CliError::InvalidName));
@@ -51,70 +84,241 @@ fn parse_scenario_path(path: &str) -> Result<i32, CliError> {
     Ok(depth)
 }
 
-fn load_scenario(path: &str) -> Board {
-    let path = Path::new(path);
-    let display = path.display();
-    let mut f = match File::open(&path) {
-        Err(why) => panic!("couldn't open {}: {}", display, Error::description(&why)),
-        Ok(file) => file,
-    };
+fn load_scenario(path: &str) -> Result<Board, CliError> {
+    let mut f = try!(File::open(&Path::new(path)));
     let mut s = String::new();
-    match f.read_to_string(&mut s) {
-        Err(why) => panic!("couldn't read {}: {}", display,
-                                                   Error::description(&why)),
-        Ok(_) => {},
-    }
-    Board::from_str(&s)
+    try!(f.read_to_string(&mut s));
+    Ok(try!(Board::from_str(&s)))
 }
 
-fn run_scenario(path: &str, maybe_depth: Option<i32>) -> Result<bool, CliError> {
-    let depth = match parse_scenario_path(path) {
+#[derive(Default)]
+struct Opts {
+    depth: Option<i32>,
+    budget: Option<Duration>,
+    stats: bool,
+    search: SearchOptions,
+    // Iterations for MCTS, which is used instead of Minimax if set
+    mcts: Option<u64>,
+    // Number of best moves to list, for fixed-depth searches
+    multipv: Option<usize>,
+    positions: Vec<String>,
+}
+
+fn run_scenario(path: &str, opts: &Opts) -> Result<bool, CliError> {
+    let b = try!(load_scenario(path));
+    run_board(b, parse_scenario_path(path), opts)
+}
+
+fn run_position(notation: &str, opts: &Opts) -> Result<bool, CliError> {
+    let b = try!(Board::from_notation(notation));
+    run_board(b, Err(CliError::NoDepth), opts)
+}
+
+// Searches `b` for whoever's turn it is, for the time budget if there is one, else to `depth` (or
+// the default depth if that's an error)
+fn run_board(b: Board, depth: Result<i32, CliError>, opts: &Opts) -> Result<bool, CliError> {
+    let g = PushfourGame::new();
+    let mut engine = Minimax::new();
+    engine.set_options(opts.search);
+    let player = b.turn();
+
+    if let Some(n) = opts.mcts {
+        let mut mcts = Mcts::new();
+        mcts.set_options(MctsOptions { iterations: Some(n), budget: opts.budget,
+                                       ..MctsOptions::default() });
+        println!("\n##### Scenario (MCTS: {} iterations) #####{:?}", n, b);
+        println!("Current board score: {}", b.score(player));
+        let result = match mcts.search(&g, &b) {
+            Some(r) => r,
+            None => {
+                if let Some(o) = g.outcome(&b) {
+                    println!("\nGame over: {:?} for {}\n", o, player);
+                }
+                return Ok(true);
+            }
+        };
+        let b_next = g.apply(&b, result.best_move);
+        println!("\nBest move ({} iterations):\n{:?}{:?}", result.iterations, result.best_move,
+                 b_next);
+        println!("New board score: {}\n", b_next.score(player));
+        if opts.stats { print_mcts_stats(&result); }
+        return Ok(true);
+    }
+
+    if let Some(budget) = opts.budget {
+        println!("\n##### Scenario (time: {} ms) #####{:?}", millis(budget), b);
+        println!("Current board score: {}", b.score(player));
+        let result = engine.search_timed(budget, &g, &b);
+        let b_next = g.apply(&b, result.best_move);
+        println!("\nBest move (depth {}):\n{:?}{:?}", result.depth, result.best_move, b_next);
+        println!("New board score: {}\n", b_next.score(player));
+        if let Some(p) = result.proven() { println!("Proven {}\n", p); }
+        if opts.stats { print_stats(&engine, &result); }
+        return Ok(true);
+    }
+
+    let depth = match depth {
         Ok(d) => d,
         Err(e) => {
-            if let Some(d) = maybe_depth { d } else {
+            if let Some(d) = opts.depth { d } else {
                 return Err(e);
             }
         }
     };
-    let g = PushfourGame::new(Player::Red);
-    let mut b = load_scenario(path);
-    b.next_turn();
 
     println!("\n##### Scenario (depth: {}) #####{:?}", depth, b);
-    println!("Current board score: {}", b.score(Player::Red));
-    let mv = Minimax::best_move(depth, &g, &b);
-    let b_next = g.apply(&b, mv);
-    println!("\nBest move:\n{:?}{:?}", mv, b_next);
-    println!("New board score: {}\n", b_next.score(Player::Red));
+    println!("Current board score: {}", b.score(player));
+    let lines = match opts.multipv {
+        Some(k) => engine.search_multipv(depth, k, &g, &b),
+        None => vec![engine.search_parallel(depth, &g, &b)],
+    };
+    let result = &lines[0];
+    let b_next = g.apply(&b, result.best_move);
+    println!("\nBest move:\n{:?}{:?}", result.best_move, b_next);
+    println!("New board score: {}\n", b_next.score(player));
+    if opts.multipv.is_some() { print_lines(&lines); }
+    if let Some(p) = result.proven() { println!("Proven {}\n", p); }
+    if opts.stats { print_stats(&engine, result); }
     Ok(true)
 }
 
-fn parse_opts(args: &mut core::iter::Peekable<std::env::Args>) -> Option<i32> {
-    match args.peek() {
-        Some(ref a) => if a != &"-d" { return None },
-        _ => return None
+fn print_lines(lines: &[SearchResult<Move>]) {
+    for (i, l) in lines.iter().enumerate() {
+        let pv: Vec<String> = l.pv.iter().map(|m| format!("{}:{}", m.row, m.col)).collect();
+        println!("{}. {}:{} score {}: {}", i + 1, l.best_move.row, l.best_move.col, l.score,
+                 pv.join(" "));
     }
-    args.next();
-    if let Some(ref d) = args.next() {
-        return d.parse::<i32>().ok();
+    println!("");
+}
+
+fn print_stats(engine: &Minimax<Move>, result: &SearchResult<Move>) {
+    let pv: Vec<String> = result.pv.iter().map(|m| format!("{}:{}", m.row, m.col)).collect();
+    println!("Score: {}", result.score);
+    println!("Principal variation: {}", pv.join(" "));
+    let st = &result.stats;
+    println!("Nodes: {}, leaf evaluations: {}, {} ms, {:.0} nodes/s", st.nodes, st.leaf_evals,
+             millis(st.elapsed), st.nodes_per_second());
+    let cutoffs: Vec<String> = st.cutoffs.iter().map(|c| c.to_string()).collect();
+    println!("Cutoffs by ply: {}", cutoffs.join(" "));
+    println!("Re-searches: {} PVS, {} aspiration", st.pvs_researches, st.aspiration_researches);
+    let s = engine.table_stats();
+    println!("Transposition table: {} hits, {} misses ({:.1}%), {} stores, {} cutoffs\n",
+             s.hits, s.misses, s.hit_rate() * 100.0, s.stores, s.cutoffs);
+}
+
+fn print_mcts_stats(result: &MctsResult<Move>) {
+    let mut visits = result.visits.clone();
+    visits.sort_by(|x, y| y.1.cmp(&x.1));
+    println!("Win rate: {:.3}", result.value());
+    for &(m, n, v) in visits.iter() {
+        println!("{}:{} {} visits, win rate {:.3}", m.row, m.col, n, v);
     }
-    None
+    println!("{} iterations, {} ms\n", result.iterations, millis(result.elapsed));
+}
+
+fn millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + (d.subsec_nanos() / 1000000) as u64
+}
+
+fn parse_opts(args: &mut core::iter::Peekable<std::env::Args>) -> Option<Opts> {
+    let mut opts = Opts::default();
+    loop {
+        match args.peek() {
+            Some(ref a) => if !a.starts_with("-") { break },
+            _ => break
+        }
+        let flag = args.next().unwrap();
+        if flag == "-s" {
+            opts.stats = true;
+            continue;
+        }
+        if flag == "--pvs" {
+            opts.search.pvs = true;
+            continue;
+        }
+        let val = match args.next() {
+            Some(v) => v,
+            None => return None,
+        };
+        match &*flag {
+            "-p" => opts.positions.push(val),
+            "--aspiration" => opts.search.aspiration = Some(match val.parse::<i32>() {
+                Ok(w) if w > 0 => w,
+                _ => return None
+            }),
+            "--mcts" => opts.mcts = Some(match val.parse::<u64>() {
+                Ok(n) if n > 0 => n,
+                _ => return None
+            }),
+            "--multipv" => opts.multipv = Some(match val.parse::<usize>() {
+                Ok(k) if k > 0 => k,
+                _ => return None
+            }),
+            "--extend" => opts.search.extension = match val.parse::<i32>() {
+                Ok(n) if n >= 0 => n,
+                _ => return None
+            },
+            "--solve" => opts.search.endgame = match val.parse::<u32>() {
+                Ok(n) => n,
+                _ => return None
+            },
+            "-j" => opts.search.threads = match val.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => return None
+            },
+            "-d" => opts.depth = Some(match val.parse::<i32>() { Ok(d) => d, _ => return None }),
+            "-t" => opts.budget = Some(Duration::from_millis(match val.parse::<u64>() {
+                Ok(t) => t,
+                _ => return None
+            })),
+            _ => return None,
+        }
+    }
+    Some(opts)
 }
 
 fn print_usage() {
     println!("Usage:
 
-    ./run-scenario [-d DEFAULT_DEPTH] FILE [FILE] ...
+    ./run-scenario [-d DEFAULT_DEPTH] [-t MILLIS] [-s] [--pvs] [--aspiration WIDTH]
+                   [-j THREADS] [--mcts ITERATIONS] [--multipv K]
+                   [--extend PLIES] [--solve CELLS] [-p POSITION] ... [FILE] ...
 
-where each FILE contains depth_N in its name, unless DEFAULT_DEPTH is provided.");
+where each FILE contains depth_N in its name, unless DEFAULT_DEPTH is provided.
+Each -p runs a POSITION given in one-line notation (like \"2b1/#3/4/r3 r 4x4\") in addition
+to the files; these need -d or -t.
+With -t, each scenario is searched by iterative deepening for MILLIS milliseconds instead
+of to a fixed depth. -s prints the score, principal variation and search and transposition
+table statistics after each search. --pvs turns on principal variation search, and
+--aspiration searches each iteration of -t within WIDTH of the previous one's score.
+-j splits fixed-depth searches across THREADS threads. --mcts searches with Monte Carlo
+tree search instead, for ITERATIONS iterations (or until -t runs out); -s then prints
+each move's visits. --multipv lists the best K moves of fixed-depth searches, with
+their scores and principal variations. --extend keeps searching wins and blocks of
+wins for up to PLIES plies past the search depth. --solve searches positions with fewer
+than CELLS reachable empty cells to the end of the game, proving a win, loss or draw.");
 }
 
 fn main() {
     let mut args = env::args().peekable();
     args.next();
-    let maybe_depth = parse_opts(&mut args);
+    let opts = match parse_opts(&mut args) {
+        Some(o) => o,
+        None => {
+            print_usage();
+            process::exit(1);
+        }
+    };
+    for p in opts.positions.iter() {
+        if let Err(e) = run_position(p, &opts) {
+            writeln!(io::stderr(), "{}: {}", p, e).unwrap();
+            print_usage();
+            process::exit(1);
+        }
+    }
     for a in args {
-        if !run_scenario(&a, maybe_depth).is_ok() {
+        if let Err(e) = run_scenario(&a, &opts) {
+            writeln!(io::stderr(), "{}: {}", a, e).unwrap();
             print_usage();
             process::exit(1);
         }
@@ -1,18 +1,26 @@
 use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
 use std::collections::HashSet;
+use rand::{Rng, SeedableRng, XorShiftRng};
 use overlay::Overlay;
 use util::*;
+use zobrist;
 
 // Representation of a pushfour board.
 // It's implemented as a composition of Overlays, adding logic for getting and applying available
 // moves, and some other necessities for tracking the state of the game.
 #[derive(Clone)]
 pub struct Board {
-    size: usize,
+    width: usize,
+    height: usize,
+    connect: usize,
     turn: Player,
     blues: Overlay,
     reds: Overlay,
     rocks: Overlay,
+    hash: u64,
+    plies: usize,
 }
 
 impl fmt::Debug for Board {
@@ -20,16 +28,16 @@ impl fmt::Debug for Board {
         let mut grid = String::new();
         grid.push_str("\n+ ");
         let mut col = 0;
-        while col < self.size { grid.push_str(&*format!("{} ", col)); col += 1; }
+        while col < self.width { grid.push_str(&*format!("{} ", col)); col += 1; }
         grid.push_str("\n");
         let mut row = 0;
-        while row < self.size {
+        while row < self.height {
             let mut col = 0;
             let blue_row = self.blues.main[row];
             let red_row = self.reds.main[row];
             let rock_row = self.rocks.main[row];
             grid.push_str(&*format!("{} ", row));
-            while col < self.size {
+            while col < self.width {
                 let mut val = '-';
                 let mask = 1 << col;
                 if blue_row & mask > 0 {
@@ -49,32 +57,230 @@ impl fmt::Debug for Board {
     }
 }
 
+// Why a board grid couldn't be parsed. Lines and columns count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoardParseError {
+    // There's no "+ 0 1 2 ..." line before the rows
+    MissingHeader { line: usize },
+    // A header label isn't the next column number
+    BadColumnLabel { line: usize, col: usize },
+    // The header has no column labels
+    NoColumns { line: usize },
+    // There are no rows after the header
+    MissingRows,
+    // A row doesn't start with its row number
+    BadRowLabel { line: usize, col: usize, expected: usize },
+    // A row has a different number of cells than the header has columns
+    RowLength { line: usize, expected: usize, found: usize },
+    // A cell isn't one of '-', 'b', 'r' or '#'
+    IllegalChar { line: usize, col: usize, found: char },
+    // The "Turn:" line doesn't name a player
+    BadTurn { line: usize, col: usize },
+    // Something other than a blank line follows the "Turn:" line
+    TrailingInput { line: usize },
+    // The board is wider or taller than MAX_BOARD_DIM
+    TooLarge { width: usize, height: usize },
+}
+
+impl fmt::Display for BoardParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BoardParseError::MissingHeader { line } =>
+                write!(f, "line {}: expected a '+ 0 1 2 ...' header", line),
+            BoardParseError::BadColumnLabel { line, col } =>
+                write!(f, "line {}, column {}: column labels must count up from 0", line, col),
+            BoardParseError::NoColumns { line } =>
+                write!(f, "line {}: board has no columns", line),
+            BoardParseError::MissingRows =>
+                write!(f, "board has no rows"),
+            BoardParseError::BadRowLabel { line, col, expected } =>
+                write!(f, "line {}, column {}: expected row label {}", line, col, expected),
+            BoardParseError::RowLength { line, expected, found } =>
+                write!(f, "line {}: expected {} cells, found {}", line, expected, found),
+            BoardParseError::IllegalChar { line, col, found } =>
+                write!(f, "line {}, column {}: illegal character '{}'", line, col, found),
+            BoardParseError::BadTurn { line, col } =>
+                write!(f, "line {}, column {}: expected 'Player Blue' or 'Player Red'", line, col),
+            BoardParseError::TrailingInput { line } =>
+                write!(f, "line {}: unexpected input after the turn", line),
+            BoardParseError::TooLarge { width, height } =>
+                write!(f, "{}x{} board exceeds the maximum of {} rows and columns", width, height,
+                       MAX_BOARD_DIM),
+        }
+    }
+}
+
+impl Error for BoardParseError {
+    fn description(&self) -> &str {
+        "invalid board"
+    }
+}
+
+// Why a position couldn't be read from notation (see Board::to_notation). Rows count from 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotationError {
+    // Expected 3 or 4 space-separated fields
+    FieldCount(usize),
+    BadDimensions(String),
+    TooLarge { width: usize, height: usize },
+    RowCount { expected: usize, found: usize },
+    RowLength { row: usize, expected: usize, found: usize },
+    IllegalChar { row: usize, found: char },
+    BadTurn(String),
+    BadMoveNumber(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NotationError::FieldCount(n) =>
+                write!(f, "expected 3 or 4 fields, found {}", n),
+            NotationError::BadDimensions(ref d) =>
+                write!(f, "bad dimensions '{}', expected e.g. '8x8' or '9x7c5'", d),
+            NotationError::TooLarge { width, height } =>
+                write!(f, "{}x{} board exceeds the maximum of {} rows and columns", width, height,
+                       MAX_BOARD_DIM),
+            NotationError::RowCount { expected, found } =>
+                write!(f, "expected {} rows, found {}", expected, found),
+            NotationError::RowLength { row, expected, found } =>
+                write!(f, "row {}: expected {} cells, found {}", row, expected, found),
+            NotationError::IllegalChar { row, found } =>
+                write!(f, "row {}: illegal character '{}'", row, found),
+            NotationError::BadTurn(ref t) =>
+                write!(f, "bad turn '{}', expected 'b' or 'r'", t),
+            NotationError::BadMoveNumber(ref n) =>
+                write!(f, "bad move number '{}'", n),
+        }
+    }
+}
+
+impl Error for NotationError {
+    fn description(&self) -> &str {
+        "invalid position notation"
+    }
+}
+
+// How rocks placed by Board::place_rocks() mirror each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    // Every rock has a twin at (height - 1 - row, width - 1 - col)
+    Rotational,
+    // Every rock has a twin at (row, width - 1 - col)
+    Mirror,
+}
+
+// Constraints on the rock layouts generated by Board::place_rocks()
+#[derive(Clone, Debug)]
+pub struct RockOptions {
+    pub count: usize,
+    pub symmetry: Symmetry,
+    // Reject layouts where either player could force a win within this many plies, if it were
+    // their turn. 0 skips the check.
+    pub forced_win_plies: usize,
+    // Whether a layout may block every lane into the board from one of its edges
+    pub allow_blocked_edge: bool,
+    // Random layouts to try before giving up
+    pub max_attempts: usize,
+}
+
+impl Default for RockOptions {
+    fn default() -> RockOptions {
+        RockOptions {
+            count: 4,
+            symmetry: Symmetry::None,
+            forced_win_plies: 3,
+            allow_blocked_edge: false,
+            max_attempts: 1000,
+        }
+    }
+}
+
+// Why Board::place_rocks() couldn't place rocks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RockError {
+    // There aren't enough empty cells, or they can't hold the count with the symmetry asked for
+    TooManyRocks { count: usize, empty: usize },
+    // Every layout tried broke one of the constraints
+    NoValidLayout { attempts: usize },
+}
+
+impl fmt::Display for RockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RockError::TooManyRocks { count, empty } =>
+                write!(f, "can't place {} rocks in {} empty cells", count, empty),
+            RockError::NoValidLayout { attempts } =>
+                write!(f, "no acceptable rock layout found in {} attempts", attempts),
+        }
+    }
+}
+
+impl Error for RockError {
+    fn description(&self) -> &str {
+        "can't place rocks"
+    }
+}
+
+// Splits a line into whitespace-separated tokens, each with its 1-based column
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut toks = Vec::new();
+    let mut start = None;
+    for (i, (byte, c)) in line.char_indices().enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((i + 1, byte)),
+            (Some((col, from)), true) => {
+                toks.push((col, &line[from..byte]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((col, from)) = start { toks.push((col, &line[from..])); }
+    toks
+}
+
 impl Board {
+    // A square board
     pub fn new(size: usize) -> Board {
+        Self::new_rect(size, size)
+    }
+
+    // A board `width` columns wide and `height` rows tall, each at most MAX_BOARD_DIM
+    pub fn new_rect(width: usize, height: usize) -> Board {
         Board {
             turn: Player::Blue,
-            size: size,
-            blues: Overlay::new(size),
-            reds: Overlay::new(size),
-            rocks: Overlay::new(size)
-        }
-    }
-
-    pub fn from_str(s: &str) -> Board {
-        let size = s.lines().count() - 1;
-        let mut b = Self::new(size);
-        for (row, row_str) in s.lines().enumerate() {
-            if row == 0 { continue; }
-            for (col, c) in row_str.trim()[2..size * 2 + 1].replace(" ", "").chars().enumerate() {
-                match c {
-                    BLUE_CHAR => b.set(row - 1, col, Some(Piece::Blue)),
-                    RED_CHAR => b.set(row - 1, col, Some(Piece::Red)),
-                    ROCK_CHAR => b.set(row - 1, col, Some(Piece::Rock)),
-                    _ => {},
-                }
-            }
+            width: width,
+            height: height,
+            connect: DEFAULT_CONNECT,
+            blues: Overlay::new(width, height),
+            reds: Overlay::new(width, height),
+            rocks: Overlay::new(width, height),
+            hash: 0,
+            plies: 0,
         }
-        b
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Number of pieces in a row needed to win
+    pub fn connect(&self) -> usize {
+        self.connect
+    }
+
+    pub fn set_connect(&mut self, connect: usize) {
+        assert!(connect > 0 && connect <= MAX_BOARD_DIM, "unsupported connect length {}", connect);
+        self.connect = connect;
+    }
+
+    pub fn turn(&self) -> Player {
+        self.turn
     }
 
     pub fn next_turn(&mut self) {
@@ -83,49 +289,62 @@ impl Board {
         } else {
             self.turn = Player::Blue;
         }
+        self.hash ^= zobrist::red_turn_key();
+    }
+
+    // Zobrist hash of the pieces on the board and whose turn it is. Boards that are equal hash
+    // the same regardless of the order in which their pieces were placed.
+    pub fn hash(&self) -> u64 {
+        self.hash
     }
 
-    // Get horizontal moves, given the board masks.
-    // We must call with both orthogonal board representations to get all moves.
-    fn get_axis_moves(&self, reds: &[u64], blues: &[u64],
-                      rocks: &[u64], transpose: bool) -> Vec<Move> {
+    // Where pieces pushed into a lane `len` cells long stop, coming from its low end (bit 0: the
+    // left or top edge) and from its high end. None if that end of the lane is occupied.
+    #[inline(always)]
+    fn lane_ends(combined: u64, len: usize) -> (Option<usize>, Option<usize>) {
+        // An empty lane has 63 "zeros", and its piece slides all the way across
+        let low = trailing_zero_idx(combined).map(|zeros| {
+            if zeros >= len { len - 1 } else { zeros }
+        });
+        let high = leading_zero_idx(combined).map(|zeros| 63 - zeros).and_then(|i| {
+            if i < len { Some(i) } else { None }
+        });
+        (low, high)
+    }
+
+    // Bits of the cells that are occupied in lane `i` of the rows (or columns, if `transpose`)
+    #[inline(always)]
+    fn lane(&self, i: usize, transpose: bool) -> u64 {
+        if transpose {
+            self.blues.invert[i] | self.reds.invert[i] | self.rocks.invert[i]
+        } else {
+            self.blues.main[i] | self.reds.main[i] | self.rocks.main[i]
+        }
+    }
+
+    // Get horizontal moves, or vertical ones if `transpose`. We must call with both to get all
+    // moves.
+    fn get_axis_moves(&self, transpose: bool) -> Vec<Move> {
+        let (lanes, len) = if transpose { (self.width, self.height) }
+                           else { (self.height, self.width) };
         let mut moves = Vec::new();
-        let mut row = 0;
-        while row < self.size {
-            let combined = blues[row] | reds[row] | rocks[row];
-            if let Some(zeros) = leading_zero_idx(combined) {
-                let col = 63 - zeros;
-                if col < self.size {
-                    moves.push(Move {
-                        row: if transpose { col } else { row },
-                        col: if transpose { row } else { col },
-                        player: self.turn.clone()
-                    });
-                }
+        for i in 0..lanes {
+            let (low, high) = Board::lane_ends(self.lane(i, transpose), len);
+            for j in high.into_iter().chain(low.into_iter()) {
+                moves.push(Move {
+                    row: if transpose { j } else { i },
+                    col: if transpose { i } else { j },
+                    player: self.turn.clone()
+                });
             }
-            if let Some(zeros) = trailing_zero_idx(combined) {
-                let col = if zeros > self.size { Some(self.size - 1) }
-                          else if zeros < self.size { Some(zeros) }
-                          else { None };
-                if let Some(c) = col {
-                    moves.push(Move {
-                        row: if transpose { c } else { row },
-                        col: if transpose { row } else { c },
-                        player: self.turn.clone()
-                    });
-                }
-            }
-            row += 1;
         }
         moves
     }
 
     // Get all moves, allowing duplicates
     pub fn get_moves_dirty(&self) -> Vec<Move> {
-        let mut row_moves = self.get_axis_moves(&self.blues.main, &self.reds.main,
-                                                &self.rocks.main, false);
-        let mut col_moves = self.get_axis_moves(&self.blues.invert, &self.reds.invert,
-                                                &self.rocks.invert, true);
+        let mut row_moves = self.get_axis_moves(false);
+        let mut col_moves = self.get_axis_moves(true);
         row_moves.append(&mut col_moves);
         row_moves
     }
@@ -135,20 +354,172 @@ impl Board {
         vec_to_set(&mut self.get_moves_dirty())
     }
 
-    // Get all moves, as a uniq'd Veq
+    // Get all moves, without duplicates, in a fixed order: pushes from the left edge (by row, top
+    // to bottom), then from the right edge, then from the top edge (by column, left to right) and
+    // then from the bottom edge. A cell reachable from more than one edge comes up the first
+    // time only.
     pub fn get_moves(&self) -> Vec<Move> {
-        set_to_vec(&mut self.get_moves_set())
+        let mut moves = Vec::with_capacity(2 * (self.width + self.height));
+        let mut seen = [0u64; MAX_BOARD_DIM];
+        for &(transpose, from_high) in [(false, false), (false, true),
+                                        (true, false), (true, true)].iter() {
+            let (lanes, len) = if transpose { (self.width, self.height) }
+                               else { (self.height, self.width) };
+            for i in 0..lanes {
+                let (low, high) = Board::lane_ends(self.lane(i, transpose), len);
+                if let Some(j) = if from_high { high } else { low } {
+                    let (row, col) = if transpose { (j, i) } else { (i, j) };
+                    if seen[row] & (1 << col) != 0 { continue; }
+                    seen[row] |= 1 << col;
+                    moves.push(Move { row: row, col: col, player: self.turn });
+                }
+            }
+        }
+        moves
     }
 
     pub fn set_move(&mut self, m: Move) {
         self.set(m.row, m.col, Some(m.player.to_piece()));
     }
 
+    // Plays `m` and passes the turn. Unlike cloning and calling set_move(), this changes the
+    // board in place; unmake_move() takes it back.
+    pub fn make_move(&mut self, m: Move) {
+        self.set_move(m);
+        self.next_turn();
+        self.plies += 1;
+    }
+
+    // Undoes make_move(m). `m` must be the last move made.
+    pub fn unmake_move(&mut self, m: Move) {
+        self.set(m.row, m.col, None);
+        self.next_turn();
+        self.plies -= 1;
+    }
+
+    // Number of moves made with make_move() (or recorded in the notation the board was read from)
+    pub fn plies(&self) -> usize {
+        self.plies
+    }
+
+    pub fn set_plies(&mut self, plies: usize) {
+        self.plies = plies;
+    }
+
+    // One-line notation for the position, e.g. "2b1/#3/4/r3 r 4x4 2". Fields:
+    //   - rows from top to bottom, separated by '/'. Pieces are written as in Board's Debug
+    //     output and runs of empty cells as their length.
+    //   - whose turn it is, 'b' or 'r'
+    //   - dimensions, WIDTHxHEIGHT, followed by cN if the connect length N isn't the default
+    //   - the number of moves made so far (optional when parsing)
+    pub fn to_notation(&self) -> String {
+        let mut rows = Vec::new();
+        for row in 0..self.height {
+            let mut s = String::new();
+            let mut empty = 0;
+            for col in 0..self.width {
+                let c = match self.get(row, col) {
+                    Some(Piece::Blue) => BLUE_CHAR,
+                    Some(Piece::Red) => RED_CHAR,
+                    Some(Piece::Rock) => ROCK_CHAR,
+                    None => { empty += 1; continue; }
+                };
+                if empty > 0 { s.push_str(&empty.to_string()); }
+                empty = 0;
+                s.push(c);
+            }
+            if empty > 0 { s.push_str(&empty.to_string()); }
+            rows.push(s);
+        }
+        let turn = match self.turn { Player::Blue => BLUE_CHAR, Player::Red => RED_CHAR };
+        let mut dims = format!("{}x{}", self.width, self.height);
+        if self.connect != DEFAULT_CONNECT { dims.push_str(&format!("c{}", self.connect)); }
+        format!("{} {} {} {}", rows.join("/"), turn, dims, self.plies)
+    }
+
+    // Parses the output of to_notation()
+    pub fn from_notation(s: &str) -> Result<Board, NotationError> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() < 3 || fields.len() > 4 {
+            return Err(NotationError::FieldCount(fields.len()));
+        }
+
+        let bad_dims = || NotationError::BadDimensions(fields[2].to_string());
+        let (dims, connect) = match fields[2].find('c') {
+            Some(i) => (&fields[2][..i],
+                        Some(try!(fields[2][i + 1..].parse::<usize>().map_err(|_| bad_dims())))),
+            None => (fields[2], None),
+        };
+        let wh: Vec<usize> = try!(dims.split('x').map(|d| d.parse::<usize>())
+                                      .collect::<Result<Vec<_>, _>>().map_err(|_| bad_dims()));
+        if wh.len() != 2 || wh[0] == 0 || wh[1] == 0 || connect == Some(0) {
+            return Err(bad_dims());
+        }
+        let (width, height) = (wh[0], wh[1]);
+        if width > MAX_BOARD_DIM || height > MAX_BOARD_DIM ||
+           connect.map_or(false, |c| c > MAX_BOARD_DIM) {
+            return Err(NotationError::TooLarge { width: width, height: height });
+        }
+
+        let mut b = Board::new_rect(width, height);
+        if let Some(c) = connect { b.set_connect(c); }
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != height {
+            return Err(NotationError::RowCount { expected: height, found: rows.len() });
+        }
+        for (row, row_str) in rows.iter().enumerate() {
+            let mut col = 0;
+            let mut run = 0;
+            for c in row_str.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    // Stop as soon as the run overshoots the row, before it can overflow
+                    run = run * 10 + d as usize;
+                    if col + run > width {
+                        return Err(NotationError::RowLength { row: row, expected: width,
+                                                              found: col + run });
+                    }
+                    continue;
+                }
+                col += run;
+                run = 0;
+                let piece = match c {
+                    BLUE_CHAR => Piece::Blue,
+                    RED_CHAR => Piece::Red,
+                    ROCK_CHAR => Piece::Rock,
+                    _ => return Err(NotationError::IllegalChar { row: row, found: c }),
+                };
+                if col < width { b.set(row, col, Some(piece)); }
+                col += 1;
+            }
+            col += run;
+            if col != width {
+                return Err(NotationError::RowLength { row: row, expected: width, found: col });
+            }
+        }
+
+        match fields[1] {
+            "b" => {},
+            "r" => b.next_turn(),
+            t => return Err(NotationError::BadTurn(t.to_string())),
+        }
+        if fields.len() == 4 {
+            match fields[3].parse::<usize>() {
+                Ok(n) => b.set_plies(n),
+                Err(_) => return Err(NotationError::BadMoveNumber(fields[3].to_string())),
+            }
+        }
+        Ok(b)
+    }
+
     pub fn set(&mut self, row: usize, col: usize, val: Option<Piece>) {
+        if let Some(old) = self.get(row, col) {
+            self.hash ^= zobrist::piece_key(row, col, &old);
+        }
         self.blues.clear(row, col);
         self.reds.clear(row, col);
         self.rocks.clear(row, col);
         if let Some(color) = val {
+            self.hash ^= zobrist::piece_key(row, col, &color);
             let mut overlay_to_set = match color {
                 Piece::Blue => &mut self.blues,
                 Piece::Red => &mut self.reds,
@@ -169,17 +540,115 @@ impl Board {
     // Returns whether or not current Board state is a win for `player`
     pub fn is_win_state(&self, player: Player) -> bool {
         let overlay = match player { Player::Red => &self.reds, Player::Blue => &self.blues };
-        overlay.is_win_state()
+        overlay.is_win_state(self.connect)
     }
 
     // Returns difference in lengths of each player's longest contiguous run. If a player is in a
-    // win state, add 8 extra points to their existing 4.
+    // win state, their run of N counts as 3N.
     pub fn score(&self, player: Player) -> i32 {
         let (mine, theirs) = match player {
             Player::Red => (&self.reds, &self.blues),
             Player::Blue => (&self.blues, &self.reds),
         };
-        mine.score() - theirs.score()
+        mine.score(self.connect) - theirs.score(self.connect)
+    }
+
+    // The cells holding `player`'s pieces
+    pub fn pieces(&self, player: Player) -> &Overlay {
+        match player { Player::Red => &self.reds, Player::Blue => &self.blues }
+    }
+
+    pub fn rocks(&self) -> &Overlay {
+        &self.rocks
+    }
+
+    // The cells a piece can be pushed into right now, by either player, as an Overlay
+    pub fn legal_destinations(&self) -> Overlay {
+        let mask = self.legal_mask();
+        let mut o = Overlay::new(self.width, self.height);
+        for row in 0..self.height {
+            let mut bits = mask[row];
+            while bits != 0 {
+                o.set(row, bits.trailing_zeros() as usize);
+                bits &= bits - 1;
+            }
+        }
+        o
+    }
+
+    // The same cells as a bitmask, with bit `col` of element `row` set for cell (row, col).
+    // Computed straight from the lanes, without allocating.
+    pub fn legal_mask(&self) -> [u64; MAX_BOARD_DIM] {
+        let mut mask = [0u64; MAX_BOARD_DIM];
+        for row in 0..self.height {
+            let (low, high) = Board::lane_ends(self.lane(row, false), self.width);
+            for col in low.into_iter().chain(high.into_iter()) { mask[row] |= 1 << col; }
+        }
+        for col in 0..self.width {
+            let (low, high) = Board::lane_ends(self.lane(col, true), self.height);
+            for row in low.into_iter().chain(high.into_iter()) { mask[row] |= 1 << col; }
+        }
+        mask
+    }
+
+    // Number of distinct legal moves
+    pub fn legal_move_count(&self) -> u32 {
+        self.legal_mask()[..self.height].iter().fold(0, |n, row| n + row.count_ones())
+    }
+
+    // The legal moves, by row and then by column. Unlike get_moves(), this doesn't allocate, and
+    // the iterator doesn't borrow the board, so moves can be made while iterating.
+    pub fn legal_moves(&self) -> LegalMoves {
+        LegalMoves {
+            mask: self.legal_mask(),
+            row: 0,
+            height: self.height,
+            player: self.turn,
+        }
+    }
+
+    // Empty cells that would complete `connect` in a row for `player`, reachable or not
+    pub fn threats(&self, player: Player) -> Overlay {
+        let mut blocked = self.pieces(player.other()).clone();
+        blocked.merge(&self.rocks);
+        self.pieces(player).windows(&blocked, self.connect).1
+    }
+
+    // Cells `player` could win in with their next push
+    fn winning_cells(&self, player: Player) -> [u64; MAX_BOARD_DIM] {
+        let mut cells = self.legal_mask();
+        let threats = self.threats(player);
+        for row in 0..self.height { cells[row] &= threats.main[row]; }
+        cells
+    }
+
+    // Moves the player to move can't afford to put off, by row and then by column. If they can
+    // win on the spot, these are their winning pushes. Otherwise, if the opponent could win with
+    // their next push, they're every push that leaves the opponent without one, whether by
+    // filling the winning cell or by cutting off the lanes that lead to it. That makes the list
+    // complete, so a loss the search finds by following only these is a real one; if no push
+    // stops the opponent, the first legal push stands in for all of them. Otherwise nothing is
+    // forcing.
+    pub fn forcing_moves(&self) -> Vec<Move> {
+        let (me, them) = (self.turn, self.turn.other());
+        let wins: Vec<Move> = LegalMoves {
+            mask: self.winning_cells(me),
+            row: 0,
+            height: self.height,
+            player: me,
+        }.collect();
+        if !wins.is_empty() { return wins; }
+        if self.winning_cells(them).iter().all(|&row| row == 0) { return Vec::new(); }
+
+        let mut b = self.clone();
+        let mut blocks = Vec::new();
+        for m in self.legal_moves() {
+            b.make_move(m);
+            if b.winning_cells(them).iter().all(|&row| row == 0) { blocks.push(m); }
+            b.unmake_move(m);
+        }
+        if blocks.is_empty() { blocks.extend(self.legal_moves().next()); }
+        blocks
     }
 
     // Returns an Overlay that contains all currently-populated coordinates.
@@ -206,7 +675,231 @@ impl Board {
             Player::Blue => (&self.blues, &self.reds),
         };
         let r = self.reachable();
-        mine.score_with_mask(&r) - theirs.score_with_mask(&r)
+        mine.score_with_mask(&r, self.connect) - theirs.score_with_mask(&r, self.connect)
+    }
+
+    // Places rocks on empty cells at random, deterministically for a given `seed`, retrying until
+    // the layout meets `opts`. On failure the board is left as it was.
+    pub fn place_rocks(&mut self, seed: u64, opts: &RockOptions) -> Result<(), RockError> {
+        // Groups of empty cells that must hold rocks together under the symmetry
+        let mut orbits: Vec<Vec<(usize, usize)>> = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if self.get(row, col).is_some() { continue; }
+                let twin = match opts.symmetry {
+                    Symmetry::None => (row, col),
+                    Symmetry::Rotational => (self.height - 1 - row, self.width - 1 - col),
+                    Symmetry::Mirror => (row, self.width - 1 - col),
+                };
+                if twin < (row, col) { continue; }
+                if twin == (row, col) {
+                    orbits.push(vec![(row, col)]);
+                } else if self.get(twin.0, twin.1).is_none() {
+                    orbits.push(vec![(row, col), twin]);
+                }
+            }
+        }
+        let empty = orbits.iter().map(|o| o.len()).fold(0, |a, b| a + b);
+        let singles = orbits.iter().filter(|o| o.len() == 1).count();
+        if opts.count > empty || (opts.count % 2 == 1 && singles == 0) {
+            return Err(RockError::TooManyRocks { count: opts.count, empty: empty });
+        }
+
+        let mut rng: XorShiftRng = SeedableRng::from_seed([seed as u32, (seed >> 32) as u32,
+                                                           0x7c1b_93e5, 0x3d4a_f062]);
+        for _ in 0..opts.max_attempts {
+            let mut b = self.clone();
+            let mut left = orbits.clone();
+            let mut placed = 0;
+            while placed < opts.count {
+                let fits: Vec<usize> = (0..left.len())
+                    .filter(|&i| left[i].len() <= opts.count - placed).collect();
+                if fits.is_empty() { break; }
+                let orbit = left.swap_remove(fits[rng.gen_range(0, fits.len())]);
+                for &(row, col) in orbit.iter() { b.set(row, col, Some(Piece::Rock)); }
+                placed += orbit.len();
+            }
+            if placed < opts.count { continue; }
+            if !opts.allow_blocked_edge && b.has_blocked_edge() { continue; }
+            if opts.forced_win_plies > 0 &&
+               (b.has_forced_win(Player::Blue, opts.forced_win_plies) ||
+                b.has_forced_win(Player::Red, opts.forced_win_plies)) {
+                continue;
+            }
+            *self = b;
+            return Ok(());
+        }
+        Err(RockError::NoValidLayout { attempts: opts.max_attempts })
+    }
+
+    // Whether some edge has no empty cell, so no piece can be pushed in from that side
+    pub fn has_blocked_edge(&self) -> bool {
+        let (w, h) = (self.width, self.height);
+        let full_row = |row| (0..w).all(|col| self.get(row, col).is_some());
+        let full_col = |col| (0..h).all(|row| self.get(row, col).is_some());
+        full_row(0) || full_row(h - 1) || full_col(0) || full_col(w - 1)
+    }
+
+    // Whether `player`, if it were their turn, could force a win within `plies` plies
+    pub fn has_forced_win(&self, player: Player, plies: usize) -> bool {
+        let mut b = self.clone();
+        if b.turn != player { b.next_turn(); }
+        b.forces_win(player, plies)
+    }
+
+    fn forces_win(&mut self, player: Player, plies: usize) -> bool {
+        // Can't win without enough pieces to connect
+        let pieces = self.pieces(player).count() as usize;
+        let my_moves = if self.turn == player { (plies + 1) / 2 } else { plies / 2 };
+        if plies == 0 || pieces + my_moves < self.connect { return false; }
+
+        if self.turn == player {
+            for m in self.legal_moves() {
+                self.make_move(m);
+                let won = self.is_win_state(player) || self.forces_win(player, plies - 1);
+                self.unmake_move(m);
+                if won { return true; }
+            }
+            false
+        } else {
+            // Every reply has to lose, and running out of replies is a draw
+            if self.legal_move_count() == 0 { return false; }
+            for m in self.legal_moves() {
+                self.make_move(m);
+                let lost = !self.is_win_state(m.player) && self.forces_win(player, plies - 1);
+                self.unmake_move(m);
+                if !lost { return false; }
+            }
+            true
+        }
+    }
+}
+
+// Iterator over a Board's legal moves; see Board::legal_moves()
+pub struct LegalMoves {
+    mask: [u64; MAX_BOARD_DIM],
+    row: usize,
+    height: usize,
+    player: Player,
+}
+
+impl Iterator for LegalMoves {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        while self.row < self.height {
+            let bits = self.mask[self.row];
+            if bits != 0 {
+                self.mask[self.row] = bits & (bits - 1);
+                return Some(Move {
+                    row: self.row,
+                    col: bits.trailing_zeros() as usize,
+                    player: self.player,
+                });
+            }
+            self.row += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.mask[self.row..self.height].iter()
+            .fold(0, |n, row| n + row.count_ones() as usize);
+        (n, Some(n))
+    }
+}
+
+// Parses a grid in the format of Board's Debug output. The width is the number of column labels
+// in the header and the height is the number of rows that follow. A final "Turn: Player Red"
+// line is optional; it's Blue's turn without one.
+impl FromStr for Board {
+    type Err = BoardParseError;
+
+    fn from_str(s: &str) -> Result<Board, BoardParseError> {
+        let mut lines = s.lines().enumerate()
+            .map(|(i, l)| (i + 1, tokens(l)))
+            .filter(|&(_, ref toks)| !toks.is_empty());
+
+        let (header_line, header) = match lines.next() {
+            Some(h) => h,
+            None => return Err(BoardParseError::MissingRows),
+        };
+        if header[0].1 != "+" {
+            return Err(BoardParseError::MissingHeader { line: header_line });
+        }
+        for (i, &(col, label)) in header.iter().skip(1).enumerate() {
+            if label.parse::<usize>().ok() != Some(i) {
+                return Err(BoardParseError::BadColumnLabel { line: header_line, col: col });
+            }
+        }
+        let width = header.len() - 1;
+        if width == 0 { return Err(BoardParseError::NoColumns { line: header_line }); }
+
+        let mut rows = Vec::new();
+        let mut turn = Player::Blue;
+        let mut turn_line = None;
+        for (line, toks) in lines {
+            if let Some(_) = turn_line {
+                return Err(BoardParseError::TrailingInput { line: line });
+            }
+            if toks[0].1 == "Turn:" {
+                let name: Vec<&str> = toks.iter().skip(1).map(|&(_, t)| t).collect();
+                let col = if toks.len() > 1 { toks[1].0 } else { toks[0].0 + 5 };
+                turn = match &*name.join(" ") {
+                    "Player Blue" | "Blue" => Player::Blue,
+                    "Player Red" | "Red" => Player::Red,
+                    _ => return Err(BoardParseError::BadTurn { line: line, col: col }),
+                };
+                turn_line = Some(line);
+                continue;
+            }
+            let (label_col, label) = toks[0];
+            if label.parse::<usize>().ok() != Some(rows.len()) {
+                return Err(BoardParseError::BadRowLabel {
+                    line: line,
+                    col: label_col,
+                    expected: rows.len(),
+                });
+            }
+            if toks.len() - 1 != width {
+                return Err(BoardParseError::RowLength {
+                    line: line,
+                    expected: width,
+                    found: toks.len() - 1,
+                });
+            }
+            let mut cells = Vec::new();
+            for &(col, tok) in toks.iter().skip(1) {
+                let mut chars = tok.chars();
+                let c = chars.next().unwrap();
+                if let Some(extra) = chars.next() {
+                    return Err(BoardParseError::IllegalChar { line: line, col: col + 1,
+                                                              found: extra });
+                }
+                cells.push(match c {
+                    BLUE_CHAR => Some(Piece::Blue),
+                    RED_CHAR => Some(Piece::Red),
+                    ROCK_CHAR => Some(Piece::Rock),
+                    '-' => None,
+                    _ => return Err(BoardParseError::IllegalChar { line: line, col: col,
+                                                                   found: c }),
+                });
+            }
+            rows.push(cells);
+        }
+
+        if rows.is_empty() { return Err(BoardParseError::MissingRows); }
+        if width > MAX_BOARD_DIM || rows.len() > MAX_BOARD_DIM {
+            return Err(BoardParseError::TooLarge { width: width, height: rows.len() });
+        }
+        let mut b = Board::new_rect(width, rows.len());
+        for (row, cells) in rows.into_iter().enumerate() {
+            for (col, cell) in cells.into_iter().enumerate() {
+                if cell.is_some() { b.set(row, col, cell); }
+            }
+        }
+        if turn == Player::Red { b.next_turn(); }
+        Ok(b)
     }
 }
 
@@ -266,6 +959,52 @@ fn test_clone() {
     assert_eq!(Some(Piece::Blue), b.get(0, 0));
 }
 
+#[test]
+fn test_hash_transposition() {
+    let mut b = Board::new(4);
+    let empty = b.hash();
+    b.set(0, 0, Some(Piece::Blue));
+    b.set(3, 2, Some(Piece::Red));
+
+    let mut c = Board::new(4);
+    c.set(3, 2, Some(Piece::Red));
+    c.set(0, 0, Some(Piece::Blue));
+    assert_eq!(b.hash(), c.hash());
+
+    // Overwriting and clearing cells must cancel out the old keys
+    c.set(0, 0, Some(Piece::Rock));
+    assert!(b.hash() != c.hash());
+    c.set(0, 0, None);
+    c.set(3, 2, None);
+    assert_eq!(empty, c.hash());
+}
+
+#[test]
+fn test_hash_turn() {
+    let mut b = Board::new(4);
+    let blue = b.hash();
+    b.next_turn();
+    assert!(blue != b.hash());
+    b.next_turn();
+    assert_eq!(blue, b.hash());
+}
+
+#[test]
+fn test_make_unmake_move() {
+    let mut b = Board::from_str("+ 0 1 2 3
+                                 0 b - - -
+                                 1 r - - #
+                                 2 - - - b
+                                 3 - - - -").unwrap();
+    let before = (format!("{:?}", b), b.hash());
+    let m = Move { row: 3, col: 3, player: Player::Blue };
+    b.make_move(m);
+    assert_eq!(Some(Piece::Blue), b.get(3, 3));
+    assert!(!b.is_win_state(Player::Blue));
+    b.unmake_move(m);
+    assert_eq!(before, (format!("{:?}", b), b.hash()));
+}
+
 #[test]
 fn test_get_moves_basic_2() {
     let mut b = Board::new(2);
@@ -321,6 +1060,46 @@ fn test_get_moves_empty_3() {
     assert_eq!(vec_to_set(&mut expected), vec_to_set(&mut b.get_moves()));
 }
 
+#[test]
+fn test_get_moves_rect() {
+    let mut b = Board::new_rect(3, 2);
+    b.set(0, 1, Some(Piece::Rock)); // 0 # 0
+    b.set(1, 2, Some(Piece::Red));  // 0 0 r
+    let mut expected = vec![
+        Move { row: 0, col: 0, player: Player::Blue },
+        Move { row: 0, col: 2, player: Player::Blue },
+        Move { row: 1, col: 0, player: Player::Blue },
+        Move { row: 1, col: 1, player: Player::Blue },
+    ];
+    assert_eq!(vec_to_set(&mut expected), vec_to_set(&mut b.get_moves()));
+}
+
+#[test]
+fn test_get_moves_max_size() {
+    let b = Board::new(MAX_BOARD_DIM);
+    let edge = MAX_BOARD_DIM - 1;
+    let moves = b.get_moves_set();
+    assert_eq!(4 * edge, moves.len());
+    assert!(moves.contains(&Move { row: edge, col: 0, player: Player::Blue }));
+    assert!(moves.contains(&Move { row: 5, col: edge, player: Player::Blue }));
+}
+
+#[test]
+fn test_board_from_str_rect() {
+    let s = "+ 0 1 2 3 4 5 6 7 8 9 10 11
+             0 b - - - - - - - - - - r
+             1 - - - - - - - - - - r -
+             2 - - - - - - - - - r - -
+             3 - - - - - - - - r - - #";
+    let b = Board::from_str(s).unwrap();
+    assert_eq!((12, 4), (b.width(), b.height()));
+    assert_eq!(Some(Piece::Blue), b.get(0, 0));
+    assert_eq!(Some(Piece::Red), b.get(0, 11));
+    assert_eq!(Some(Piece::Rock), b.get(3, 11));
+    assert!(b.is_win_state(Player::Red));
+    assert!(!b.is_win_state(Player::Blue));
+}
+
 #[test]
 fn test_board_from_str() {
     let s = "+ 0 1 2 3
@@ -328,7 +1107,7 @@ fn test_board_from_str() {
              1 r - - #
              2 - - - b
              3 - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(Some(Piece::Blue), b.get(0, 0));
     assert_eq!(Some(Piece::Red), b.get(1, 0));
     assert_eq!(Some(Piece::Rock), b.get(1, 3));
@@ -343,7 +1122,7 @@ fn test_score_blank() {
              2 - - - - -
              3 - - - - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Blue), 0);
 }
 
@@ -355,7 +1134,7 @@ fn test_score_even_1() {
              2 - - r - -
              3 - - - - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Blue), 0);
 }
 
@@ -367,7 +1146,7 @@ fn test_score_even_2() {
              2 - - r - -
              3 - - r - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Blue), 0);
 }
 
@@ -379,7 +1158,7 @@ fn test_score_even_3() {
              2 - - r - -
              3 - - r r -
              4 - - - - r";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Blue), 0);
 }
 
@@ -391,7 +1170,7 @@ fn test_score_adv_1() {
              2 - - r - -
              3 - - r - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Blue), 1);
 }
 
@@ -403,7 +1182,7 @@ fn test_score_win() {
              2 - - r - -
              3 - - r - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Blue), 10);
 }
 
@@ -418,7 +1197,7 @@ fn test_score_lose() {
              5 - - - b b - - r
              6 - - - - - r - r
              7 - - - - - r r b";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score(Player::Red), -9);
 }
 
@@ -430,7 +1209,7 @@ fn test_score_reachable_win() {
              2 - - r - -
              3 - - r - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score_reachable(Player::Blue), 10);
 }
 
@@ -442,7 +1221,7 @@ fn test_score_reachable_basic() {
              2 - - r - -
              3 - - r - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score_reachable(Player::Blue), 1);
 }
 
@@ -454,7 +1233,7 @@ fn test_score_reachable_landlock() {
              2 - - r - -
              3 - - r - -
              4 - - - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score_reachable(Player::Blue), -1);
 }
 
@@ -466,6 +1245,291 @@ fn test_score_reachable_unreachable() {
              2 - - r - -
              3 - - r - -
              4 - r - - -";
-    let b = Board::from_str(s);
+    let b = Board::from_str(s).unwrap();
     assert_eq!(b.score_reachable(Player::Blue), -1);
 }
+
+#[test]
+fn test_connect_3() {
+    let s = "+ 0 1 2 3 4
+             0 - - - - -
+             1 - b - # -
+             2 - - b - -
+             3 - - - b -
+             4 r r - - -";
+    let mut b = Board::from_str(s).unwrap();
+    assert!(!b.is_win_state(Player::Blue));
+    b.set_connect(3);
+    assert!(b.is_win_state(Player::Blue));
+    assert_eq!(b.score(Player::Blue), 7);
+    assert_eq!(b.score_reachable(Player::Blue), 7);
+}
+
+#[test]
+fn test_connect_5() {
+    let s = "+ 0 1 2 3 4 5 6
+             0 - - - - - - -
+             1 b b b b - - -
+             2 - - - - - - -
+             3 r r r # r r -";
+    let mut b = Board::from_str(s).unwrap();
+    b.set_connect(5);
+    assert!(!b.is_win_state(Player::Blue));
+    // Red's runs are blocked by the rock, so only blue has a completable line
+    assert_eq!(b.score_reachable(Player::Blue), 4);
+    b.set(1, 4, Some(Piece::Blue));
+    assert!(b.is_win_state(Player::Blue));
+    assert_eq!(b.score(Player::Blue), 15 - 3);
+}
+
+#[test]
+fn test_board_from_str_turn() {
+    let b = Board::new_rect(3, 2);
+    let mut expected = b.clone();
+    expected.next_turn();
+    // Debug output round-trips, turn included
+    let parsed = Board::from_str(&format!("{:?}", expected)).unwrap();
+    assert_eq!(Player::Red, parsed.turn());
+    assert_eq!(expected.hash(), parsed.hash());
+}
+
+#[test]
+fn test_board_from_str_errors() {
+    assert_eq!(Err(BoardParseError::MissingRows), Board::from_str("").map(|_| ()));
+    assert_eq!(Err(BoardParseError::MissingRows), Board::from_str("+ 0 1").map(|_| ()));
+    assert_eq!(Err(BoardParseError::MissingHeader { line: 1 }),
+               Board::from_str("0 b -\n1 - -").map(|_| ()));
+    assert_eq!(Err(BoardParseError::BadColumnLabel { line: 1, col: 5 }),
+               Board::from_str("+ 0 2\n0 b -").map(|_| ()));
+    assert_eq!(Err(BoardParseError::BadRowLabel { line: 3, col: 1, expected: 1 }),
+               Board::from_str("+ 0 1\n0 b -\n0 - -").map(|_| ()));
+    assert_eq!(Err(BoardParseError::RowLength { line: 3, expected: 2, found: 3 }),
+               Board::from_str("+ 0 1\n0 b -\n1 - - r").map(|_| ()));
+    assert_eq!(Err(BoardParseError::IllegalChar { line: 2, col: 5, found: 'x' }),
+               Board::from_str("+ 0 1\n0 b x\n1 - -").map(|_| ()));
+    assert_eq!(Err(BoardParseError::IllegalChar { line: 2, col: 4, found: 'b' }),
+               Board::from_str("+ 0 1\n0 bb -\n1 - -").map(|_| ()));
+    assert_eq!(Err(BoardParseError::BadTurn { line: 4, col: 7 }),
+               Board::from_str("+ 0 1\n0 b -\n1 - -\nTurn: Purple").map(|_| ()));
+    assert_eq!(Err(BoardParseError::TrailingInput { line: 5 }),
+               Board::from_str("+ 0 1\n0 b -\n1 - -\nTurn: Red\n2 - -").map(|_| ()));
+    assert_eq!(Err(BoardParseError::NoColumns { line: 1 }),
+               Board::from_str("+\n0").map(|_| ()));
+    assert_eq!(Err(BoardParseError::NoColumns { line: 2 }),
+               Board::from_str("\n+\n0\n1").map(|_| ()));
+    let labels: Vec<String> = (0..64).map(|i| i.to_string()).collect();
+    let wide = format!("+ {}\n0 {}", labels.join(" "), vec!["-"; 64].join(" "));
+    assert_eq!(Err(BoardParseError::TooLarge { width: 64, height: 1 }),
+               Board::from_str(&wide).map(|_| ()));
+    let tall: Vec<String> = (0..64).map(|i| format!("{} -", i)).collect();
+    assert_eq!(Err(BoardParseError::TooLarge { width: 1, height: 64 }),
+               Board::from_str(&format!("+ 0\n{}", tall.join("\n"))).map(|_| ()));
+}
+
+#[test]
+fn test_notation() {
+    let s = "+ 0 1 2 3
+             0 - - b -
+             1 # - - -
+             2 - - - -
+             3 r - - -";
+    let b = Board::from_str(s).unwrap();
+    assert_eq!("2b1/#3/4/r3 b 4x4 0", b.to_notation());
+}
+
+#[test]
+fn test_notation_round_trip() {
+    let s = "+ 0 1 2 3 4 5 6 7 8 9 10 11
+             0 b - - - - - - - - - - r
+             1 - - - - - - - - - - r -
+             2 - - - - - - - - - - - -
+             3 - - - - - - - - r - - #";
+    let mut b = Board::from_str(s).unwrap();
+    b.set_connect(5);
+    b.make_move(Move { row: 2, col: 0, player: Player::Blue });
+    let n = b.to_notation();
+    assert_eq!("b10r/10r1/b11/8r2# r 12x4c5 1", n);
+    let parsed = Board::from_notation(&n).unwrap();
+    assert_eq!(format!("{:?}", b), format!("{:?}", parsed));
+    assert_eq!((b.hash(), b.connect(), b.plies()),
+               (parsed.hash(), parsed.connect(), parsed.plies()));
+    assert_eq!(n, parsed.to_notation());
+
+    // The move number is optional
+    assert_eq!(0, Board::from_notation("2b1/#3/4/r3 b 4x4").unwrap().plies());
+}
+
+#[test]
+fn test_notation_errors() {
+    assert_eq!(Err(NotationError::FieldCount(2)), Board::from_notation("4/4 b").map(|_| ()));
+    assert_eq!(Err(NotationError::BadDimensions("4y4".to_string())),
+               Board::from_notation("4/4 b 4y4").map(|_| ()));
+    assert_eq!(Err(NotationError::TooLarge { width: 64, height: 1 }),
+               Board::from_notation("64 b 64x1").map(|_| ()));
+    assert_eq!(Err(NotationError::RowCount { expected: 3, found: 2 }),
+               Board::from_notation("4/4 b 4x3").map(|_| ()));
+    assert_eq!(Err(NotationError::RowLength { row: 1, expected: 4, found: 5 }),
+               Board::from_notation("4/b4 b 4x2").map(|_| ()));
+    assert_eq!(Err(NotationError::RowLength { row: 0, expected: 4, found: 9 }),
+               Board::from_notation("99999999999999999999999 b 4x1").map(|_| ()));
+    assert_eq!(Err(NotationError::RowLength { row: 0, expected: 4, found: 5 }),
+               Board::from_notation("b2b18446744073709551615 b 4x1").map(|_| ()));
+    assert_eq!(Err(NotationError::IllegalChar { row: 0, found: 'x' }),
+               Board::from_notation("3x/4 b 4x2").map(|_| ()));
+    assert_eq!(Err(NotationError::BadTurn("g".to_string())),
+               Board::from_notation("4/4 g 4x2").map(|_| ()));
+    assert_eq!(Err(NotationError::BadMoveNumber("-1".to_string())),
+               Board::from_notation("4/4 r 4x2 -1").map(|_| ()));
+}
+
+#[test]
+fn test_place_rocks_seeded() {
+    let opts = RockOptions::default();
+    let mut a = Board::new(8);
+    let mut b = Board::new(8);
+    a.place_rocks(42, &opts).unwrap();
+    b.place_rocks(42, &opts).unwrap();
+    assert_eq!(a.to_notation(), b.to_notation());
+    assert_eq!(4, a.to_notation().matches(ROCK_CHAR).count());
+    // Very likely different for another seed
+    b = Board::new(8);
+    b.place_rocks(43, &opts).unwrap();
+    assert!(a.to_notation() != b.to_notation());
+}
+
+#[test]
+fn test_place_rocks_symmetry() {
+    for seed in 0..20 {
+        let opts = RockOptions { count: 5, symmetry: Symmetry::Rotational, ..Default::default() };
+        let mut b = Board::new_rect(7, 5);
+        b.place_rocks(seed, &opts).unwrap();
+        let opts = RockOptions { count: 6, symmetry: Symmetry::Mirror, ..Default::default() };
+        let mut m = Board::new(8);
+        m.place_rocks(seed, &opts).unwrap();
+        for row in 0..5 {
+            for col in 0..7 {
+                assert_eq!(b.get(row, col), b.get(4 - row, 6 - col));
+            }
+        }
+        for row in 0..8 {
+            for col in 0..8 {
+                assert_eq!(m.get(row, col), m.get(row, 7 - col));
+            }
+        }
+        assert_eq!(5, b.to_notation().matches(ROCK_CHAR).count());
+        assert_eq!(6, m.to_notation().matches(ROCK_CHAR).count());
+    }
+}
+
+#[test]
+fn test_place_rocks_errors() {
+    let mut b = Board::new(2);
+    let opts = RockOptions { count: 5, ..Default::default() };
+    assert_eq!(Err(RockError::TooManyRocks { count: 5, empty: 4 }), b.place_rocks(1, &opts));
+    // An 8x8 board has no cell that is its own mirror image
+    let opts = RockOptions { count: 3, symmetry: Symmetry::Mirror, ..Default::default() };
+    assert_eq!(Err(RockError::TooManyRocks { count: 3, empty: 64 }),
+               Board::new(8).place_rocks(1, &opts));
+    // With two in a row to win, whoever moves first wins on their second move
+    b.set_connect(2);
+    let opts = RockOptions { count: 0, max_attempts: 10, ..Default::default() };
+    assert_eq!(Err(RockError::NoValidLayout { attempts: 10 }), b.place_rocks(1, &opts));
+    // Three rocks on a 2x2 board always fill an edge
+    let mut b = Board::new(2);
+    let opts = RockOptions { count: 3, max_attempts: 10, ..Default::default() };
+    assert_eq!(Err(RockError::NoValidLayout { attempts: 10 }), b.place_rocks(1, &opts));
+    let opts = RockOptions { allow_blocked_edge: true, ..opts };
+    assert_eq!(Ok(()), b.place_rocks(1, &opts));
+}
+
+#[test]
+fn test_forced_win() {
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 - - - - -
+                             3 - - - - -
+                             4 - b b - -").unwrap();
+    // Blue threatens both ends of the row at once
+    assert!(!b.has_forced_win(Player::Blue, 1));
+    assert!(b.has_forced_win(Player::Blue, 3));
+    assert!(!b.has_forced_win(Player::Red, 3));
+}
+
+#[test]
+fn test_get_moves_order() {
+    let b = Board::from_str("+ 0 1 2 3
+                             0 - b - #
+                             1 - - - -
+                             2 r - - -").unwrap();
+    let moves: Vec<(usize, usize)> = b.get_moves().iter().map(|m| (m.row, m.col)).collect();
+    // Left edge: rows 0 and 1. Right edge: rows 1 and 2. Top edge: columns 0 (a repeat of
+    // (1, 0)) and 2. Bottom edge: columns 1, 2 and 3 (a repeat of (1, 3)).
+    assert_eq!(vec![(0, 0), (1, 3), (1, 0), (2, 1), (2, 2), (1, 1), (0, 2)], moves);
+    assert_eq!(b.get_moves_set().len(), moves.len());
+    for m in b.get_moves() { assert!(b.get_moves_set().contains(&m)); }
+}
+
+#[test]
+fn test_legal_mask() {
+    let b = Board::from_str("+ 0 1 2 3
+                             0 - b - #
+                             1 - - - -
+                             2 r - - -").unwrap();
+    let mask = b.legal_mask();
+    assert_eq!([0b0101, 0b1011, 0b0110], [mask[0], mask[1], mask[2]]);
+    assert!(mask[3..].iter().all(|&row| row == 0));
+    assert_eq!(7, b.legal_move_count());
+    let moves: Vec<Move> = b.legal_moves().collect();
+    let cells: Vec<(usize, usize)> = moves.iter().map(|m| (m.row, m.col)).collect();
+    assert_eq!(vec![(0, 0), (0, 2), (1, 0), (1, 1), (1, 3), (2, 1), (2, 2)], cells);
+    assert_eq!(b.get_moves_set(), vec_to_set(&mut moves.clone()));
+    assert_eq!((7, Some(7)), b.legal_moves().size_hint());
+    let o = b.legal_destinations();
+    assert!(o.get(1, 3) && o.get(0, 2) && !o.get(0, 1) && o.count() == 7);
+
+    let mut full = Board::new(2);
+    for row in 0..2 { for col in 0..2 { full.set(row, col, Some(Piece::Rock)); } }
+    assert_eq!(0, full.legal_move_count());
+    assert_eq!(None, full.legal_moves().next());
+}
+
+#[test]
+fn test_forcing_moves() {
+    let cells = |moves: Vec<Move>| -> Vec<(usize, usize)> {
+        moves.iter().map(|m| (m.row, m.col)).collect()
+    };
+    // Blue threatens 4:3, which Red can fill from the top, or cut off by pushing up into 0:3
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 - - - - -
+                             3 - - - - -
+                             4 b b b - #
+                             Turn: Player Red").unwrap();
+    assert_eq!(vec![(0, 3), (4, 3)], cells(b.forcing_moves()));
+    assert!(b.forcing_moves().iter().all(|m| m.player == Player::Red));
+    // Blue threatens 1:0 and 4:3, and Red can't stop both
+    let mut b = Board::from_str("+ 0 1 2 3 4
+                                 0 - - - - -
+                                 1 - - - - -
+                                 2 b - - - -
+                                 3 b - - - r
+                                 4 b b b - r
+                                 Turn: Player Red").unwrap();
+    assert_eq!(b.legal_moves().take(1).collect::<Vec<Move>>(), b.forcing_moves());
+    assert!(b.threats(Player::Red).count() == 0);
+    // For Blue, they're wins
+    b.make_move(Move { row: 0, col: 4, player: Player::Red });
+    assert_eq!(vec![(1, 0), (4, 3)], cells(b.forcing_moves()));
+    // Threats that can't be reached yet don't force anything
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 b b b - #
+                             3 - - - - -
+                             4 - - - - -").unwrap();
+    assert!(b.threats(Player::Blue).get(2, 3));
+    assert_eq!(Vec::<Move>::new(), b.forcing_moves());
+    assert_eq!(Vec::<Move>::new(), Board::new(4).forcing_moves());
+}
@@ -1,93 +1,111 @@
 use util::*;
 
-#[derive(Clone, Copy, Debug)]
-struct DiagLookup {
-    main: [[Coord; BOARD_SIZE]; BOARD_SIZE],
-    rot: [[Coord; BOARD_SIZE]; BOARD_SIZE]
+/* Translates regular board coordinates to coordinates in two diagonal rotations of the board
+ * ('main' and 'rot'). The diagonal representations are only used for detecting diagonal win
+ * states, and the lookup is only used for *setting* bits in them.
+ *
+ *       00            02
+ *     10  01        01  12
+ *   20  11  02    00  11  22   -- Representations ('00' means 'top left on real board')
+ *     21  12        10  21
+ *       22            22
+ *
+ *    00 11 22      20 10 00
+ *    10 21 31      30 21 11    -- Lookup results
+ *    20 30 40      40 31 22
+ *
+ *      (1)           (2)
+ *
+ * Each row of a representation is one diagonal of the board, and neighbors along a diagonal are
+ * neighboring bits. For a board `height` rows tall and `width` columns wide:
+ *
+ *   (1) The cell (r, c) is on anti-diagonal r + c. Diagonals are numbered from the top left
+ *       corner, and each one is read from its bottom left end.
+ *   (2) The cell (r, c) is on diagonal width - 1 + r - c. Diagonals are numbered from the top
+ *       right corner, and each one is read from its top left end.
+ *
+ * There are width + height - 1 diagonals in each representation.
+ */
+#[inline(always)]
+pub fn lookup(width: usize, height: usize, row: usize, col: usize) -> (Coord, Coord) {
+    let main = Coord(row + col, if col < height - row { col } else { height - 1 - row });
+    let rot = Coord(width - 1 + row - col, if row < col { row } else { col });
+    (main, rot)
 }
 
-impl DiagLookup {
-    /* This struct contains lookup tables for translating regular board coordinates to
-     * coordinates in two diagonal rotations of the board ('main' and 'rot').
-     * The diagonal representations are only used for detecting diagonal win states.
-     * The lookup tables are only used for *setting* bits in the diagonal representations.
-     *
-     *       00            02
-     *     10  01        01  12
-     *   20  11  02    00  11  22   -- Representations ('00' means 'top left on real board')
-     *     21  12        10  21
-     *       22            22
-     *
-     *    00 11 22      20 10 00
-     *    10 21 31      30 21 11    -- Lookup tables
-     *    20 30 40      40 31 22
-     *
-     *      (1)           (2)
-     *
-     *    TODO: Alternative lookup table instead of (2), which results in an inversion
-     *    of representation (1). This lookup table is obtained by flipping (1)'s
-     *    lookup table about its middle row:
-     *
-     *      20 30 40
-     *      10 21 31
-     *      00 11 22
-     */
-    pub fn new(size: usize) -> DiagLookup {
-        let mut main =  [[Coord(0, 0); BOARD_SIZE]; BOARD_SIZE];
-        let mut rot = [[Coord(0, 0); BOARD_SIZE]; BOARD_SIZE];
-        let mut key_row_reset = 1;
-        let mut key_col_reset = 1;
-        let mut key_row = 0;
-        let mut key_col = 0;
-        let mut val_row = 0;
-        let mut val_col = 0;
-        let mut total = 0;
-        while total < size * size {
-            main[key_row][key_col] = Coord(val_row, val_col);
-            let (key_row_rot, key_col_rot) = rotate_cw(size, key_row, key_col);
-            rot[key_row_rot][key_col_rot] = Coord(val_row, val_col);
+// Number of cells on diagonal `diag` of either representation
+#[inline(always)]
+pub fn diag_len(width: usize, height: usize, diag: usize) -> usize {
+    let first = if diag >= width { diag + 1 - width } else { 0 };
+    let last = if diag < height { diag } else { height - 1 };
+    last + 1 - first
+}
 
-            // Reset from top row to the left column
-            if key_row == 0 && key_row_reset < size {
-                key_row = key_row_reset;
-                key_col = 0;
-                key_row_reset += 1;
-                val_col = 0;
-                val_row += 1;
-                // Reset from the right column to the bottom row
-            } else if key_col == size - 1 && key_col_reset < size {
-                key_col = key_col_reset;
-                key_row = size - 1;
-                key_col_reset += 1;
-                val_col = 0;
-                val_row += 1;
-                // Normal traversal up and to the right
-            } else {
-                key_row -= 1;
-                key_col += 1;
-                val_col += 1;
-            }
-            total += 1;
-        }
-        DiagLookup {
-            main: main,
-            rot: rot,
+// Inverse of lookup(): the board coordinates (row, col) of bit `idx` of diagonal `diag`, in the
+// main representation
+#[inline(always)]
+pub fn main_cell(_width: usize, height: usize, diag: usize, idx: usize) -> (usize, usize) {
+    let bottom = if diag < height { diag } else { height - 1 };
+    (bottom - idx, diag - bottom + idx)
+}
+
+// Same, in the rotated representation
+#[inline(always)]
+pub fn rot_cell(width: usize, _height: usize, diag: usize, idx: usize) -> (usize, usize) {
+    if diag + 1 >= width { (diag + 1 - width + idx, idx) } else { (idx, width - 1 - diag + idx) }
+}
+
+#[test]
+fn test_lookup_square() {
+    // Matches the diagram above
+    let expected_main = [[(0, 0), (1, 1), (2, 2)],
+                         [(1, 0), (2, 1), (3, 1)],
+                         [(2, 0), (3, 0), (4, 0)]];
+    let expected_rot = [[(2, 0), (1, 0), (0, 0)],
+                        [(3, 0), (2, 1), (1, 1)],
+                        [(4, 0), (3, 1), (2, 2)]];
+    for row in 0..3 {
+        for col in 0..3 {
+            let (main, rot) = lookup(3, 3, row, col);
+            assert_eq!(expected_main[row][col], (main.0, main.1));
+            assert_eq!(expected_rot[row][col], (rot.0, rot.1));
         }
     }
 }
 
-lazy_static! {
-    static ref TABLES: [DiagLookup; 7] = [
-        DiagLookup::new(2),
-        DiagLookup::new(3),
-        DiagLookup::new(4),
-        DiagLookup::new(5),
-        DiagLookup::new(6),
-        DiagLookup::new(7),
-        DiagLookup::new(8),
-    ];
+#[test]
+fn test_lookup_rect_neighbors() {
+    // Stepping along a diagonal always moves to the neighboring bit of the same diagonal
+    let (width, height) = (7, 3);
+    for row in 0..height - 1 {
+        for col in 0..width - 1 {
+            let (main, _) = lookup(width, height, row + 1, col);
+            let (main_next, _) = lookup(width, height, row, col + 1);
+            assert_eq!((main.0, main.1 + 1), (main_next.0, main_next.1));
+            let (_, rot) = lookup(width, height, row, col);
+            let (_, rot_next) = lookup(width, height, row + 1, col + 1);
+            assert_eq!((rot.0, rot.1 + 1), (rot_next.0, rot_next.1));
+        }
+    }
 }
 
-pub fn lookup(size: usize, row: usize, col: usize) -> (Coord, Coord) {
-    ((*TABLES)[size - 2].main[row][col],(*TABLES)[size - 2].rot[row][col])
+#[test]
+fn test_inverse_lookup() {
+    for &(width, height) in [(3, 3), (7, 3), (2, 6), (8, 8)].iter() {
+        let mut main_lens = vec![0; width + height - 1];
+        let mut rot_lens = vec![0; width + height - 1];
+        for row in 0..height {
+            for col in 0..width {
+                let (main, rot) = lookup(width, height, row, col);
+                assert_eq!((row, col), main_cell(width, height, main.0, main.1));
+                assert_eq!((row, col), rot_cell(width, height, rot.0, rot.1));
+                main_lens[main.0] += 1;
+                rot_lens[rot.0] += 1;
+            }
+        }
+        for diag in 0..width + height - 1 {
+            assert_eq!(main_lens[diag], diag_len(width, height, diag));
+            assert_eq!(rot_lens[diag], diag_len(width, height, diag));
+        }
+    }
 }
@@ -0,0 +1,236 @@
+// Evaluation functions for PushfourGame. An Evaluator scores a board from one player's point of
+// view: the better the position is for `player`, the higher the score.
+
+use std::cmp;
+use std::sync::Arc;
+use board::Board;
+use util::*;
+
+pub trait Evaluator {
+    fn eval(&self, b: &Board, player: Player) -> i32;
+}
+
+// Board::score(): difference in the players' longest runs
+#[derive(Clone, Copy, Debug)]
+pub struct Score;
+
+impl Evaluator for Score {
+    fn eval(&self, b: &Board, player: Player) -> i32 {
+        b.score(player)
+    }
+}
+
+// Board::score_reachable(): like Score, but only counting runs that can still grow into a win
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreReachable;
+
+impl Evaluator for ScoreReachable {
+    fn eval(&self, b: &Board, player: Player) -> i32 {
+        b.score_reachable(player)
+    }
+}
+
+// Sum of other evaluators' scores, each multiplied by its weight. Clones share the features but
+// not the weights.
+#[derive(Clone)]
+pub struct Weighted {
+    features: Vec<(i32, Arc<Evaluator + Send + Sync>)>,
+}
+
+impl Weighted {
+    pub fn new() -> Weighted {
+        Weighted { features: Vec::new() }
+    }
+
+    // Adds a feature, e.g. Weighted::new().with(2, ScoreReachable).with(1, Score)
+    pub fn with<E: Evaluator + Send + Sync + 'static>(mut self, weight: i32, e: E) -> Weighted {
+        self.add(weight, e);
+        self
+    }
+
+    pub fn add<E: Evaluator + Send + Sync + 'static>(&mut self, weight: i32, e: E) {
+        self.features.push((weight, Arc::new(e)));
+    }
+
+    pub fn weights(&self) -> Vec<i32> {
+        self.features.iter().map(|&(w, _)| w).collect()
+    }
+
+    pub fn set_weight(&mut self, feature: usize, weight: i32) {
+        self.features[feature].0 = weight;
+    }
+}
+
+impl Evaluator for Weighted {
+    fn eval(&self, b: &Board, player: Player) -> i32 {
+        self.features.iter().fold(0, |sum, &(w, ref e)| sum + w * e.eval(b, player))
+    }
+}
+
+// What threat_features() finds for one player
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreatFeatures {
+    // windows[k] is the number of windows of `connect` cells in a line, with no opposing pieces
+    // or rocks, that hold k of the player's pieces
+    pub windows: Vec<u32>,
+    // Distinct cells that would complete a window and can be pushed into right now
+    pub playable: u32,
+    // Distinct cells that would complete a window but can't be reached yet
+    pub latent: u32,
+    // At least two playable threats: the opponent can't block them all with one move
+    pub double: bool,
+}
+
+pub fn threat_features(b: &Board, player: Player) -> ThreatFeatures {
+    let theirs = player.other();
+    let mine = b.pieces(player);
+    let mut blocked = b.pieces(theirs).clone();
+    blocked.merge(b.rocks());
+    let (windows, threats) = mine.windows(&blocked, b.connect());
+    let legal = b.legal_mask();
+    let playable = threats.main.iter().zip(legal.iter())
+        .fold(0, |n, (t, l)| n + (t & l).count_ones());
+    ThreatFeatures {
+        windows: windows,
+        playable: playable,
+        latent: threats.count() - playable,
+        double: playable >= 2,
+    }
+}
+
+// Scores the difference between the players' threat features, each weighted. A player who has
+// already won gets `win` instead.
+#[derive(Clone, Debug)]
+pub struct Threats {
+    // Weight of an open window by how many pieces it holds, starting at 1. Counts past the end
+    // of the list use the last weight.
+    pub windows: Vec<i32>,
+    pub playable: i32,
+    pub latent: i32,
+    pub double: i32,
+    pub win: i32,
+}
+
+impl Default for Threats {
+    fn default() -> Threats {
+        Threats {
+            windows: vec![1, 4, 16],
+            playable: 64,
+            latent: 24,
+            double: 256,
+            win: 10000,
+        }
+    }
+}
+
+impl Threats {
+    fn eval_player(&self, b: &Board, player: Player) -> i32 {
+        if b.is_win_state(player) { return self.win; }
+        let f = threat_features(b, player);
+        let mut score = 0;
+        for (k, &n) in f.windows.iter().enumerate().skip(1) {
+            if self.windows.is_empty() { break; }
+            let w = self.windows[cmp::min(k, self.windows.len()) - 1];
+            score += w * n as i32;
+        }
+        score += self.playable * f.playable as i32 + self.latent * f.latent as i32;
+        if f.double { score += self.double; }
+        score
+    }
+}
+
+impl Evaluator for Threats {
+    fn eval(&self, b: &Board, player: Player) -> i32 {
+        let theirs = player.other();
+        self.eval_player(b, player) - self.eval_player(b, theirs)
+    }
+}
+
+#[cfg(test)]
+fn test_board() -> Board {
+    use std::str::FromStr;
+    Board::from_str("+ 0 1 2 3 4
+                     0 - - - - -
+                     1 # r r - -
+                     2 - - - - -
+                     3 - - - - -
+                     4 - b b b #").unwrap()
+}
+
+#[test]
+fn test_builtin_evaluators() {
+    let b = test_board();
+    for &p in [Player::Blue, Player::Red].iter() {
+        assert_eq!(b.score(p), Score.eval(&b, p));
+        assert_eq!(b.score_reachable(p), ScoreReachable.eval(&b, p));
+    }
+}
+
+#[test]
+fn test_weighted() {
+    let b = test_board();
+    let mut w = Weighted::new().with(3, Score).with(-1, ScoreReachable);
+    assert_eq!(vec![3, -1], w.weights());
+    assert_eq!(3 * b.score(Player::Blue) - b.score_reachable(Player::Blue),
+               w.eval(&b, Player::Blue));
+    w.set_weight(1, 0);
+    assert_eq!(3 * b.score(Player::Red), w.eval(&b, Player::Red));
+    assert_eq!(0, Weighted::new().eval(&b, Player::Red));
+}
+
+#[test]
+fn test_threat_features() {
+    use std::str::FromStr;
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 - - - - -
+                             3 - - - - -
+                             4 # b b b -").unwrap();
+    let f = threat_features(&b, Player::Blue);
+    assert_eq!((1, 0, false), (f.playable, f.latent, f.double));
+    assert_eq!(1, f.windows[3]);
+    assert_eq!(0, f.windows[4]);
+    assert_eq!(ThreatFeatures { windows: vec![19, 0, 0, 0, 0], ..Default::default() },
+               threat_features(&b, Player::Red));
+
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 - - - - -
+                             3 - - - - -
+                             4 - b b b -").unwrap();
+    let f = threat_features(&b, Player::Blue);
+    assert_eq!((2, 0, true), (f.playable, f.latent, f.double));
+
+    // (3, 3) can't be reached from any edge yet
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 r - - - -
+                             1 - r - - -
+                             2 - - r - -
+                             3 - - - - -
+                             4 - - - - -").unwrap();
+    let f = threat_features(&b, Player::Red);
+    assert_eq!((0, 1, false), (f.playable, f.latent, f.double));
+}
+
+#[test]
+fn test_threats_eval() {
+    let b = test_board();
+    let t = Threats::default();
+    assert!(t.eval(&b, Player::Blue) > 0);
+    assert_eq!(-t.eval(&b, Player::Blue), t.eval(&b, Player::Red));
+    let mut won = b.clone();
+    won.set(4, 0, Some(Piece::Blue));
+    assert!(won.is_win_state(Player::Blue));
+    assert!(t.eval(&won, Player::Blue) > t.win / 2);
+}
+
+#[test]
+fn test_threats_search() {
+    use minimax::Minimax;
+    use PushfourGame;
+    let g = PushfourGame::with_evaluator(Threats::default());
+    let mv = Minimax::best_move(2, &g, &test_board());
+    assert_eq!((4, 0), (mv.row, mv.col));
+}
@@ -5,37 +5,90 @@
 
 #[macro_use]
 extern crate lazy_static;
+extern crate rand;
 
 
 pub mod overlay;
 pub mod diag_lookup;
 pub mod board;
+pub mod eval;
+pub mod mcts;
 pub mod minimax;
+pub mod ponder;
+pub mod record;
+pub mod transposition;
 pub mod util;
+pub mod zobrist;
 
-use minimax::Game;
+use minimax::{Game, Outcome};
 use board::*;
+use eval::{Evaluator, ScoreReachable};
 use util::*;
 
-pub struct PushfourGame {
-    player: Player,
+// Pushfour for Minimax, scoring positions with an Evaluator for whoever's turn it is on the Board
+#[derive(Clone)]
+pub struct PushfourGame<E = ScoreReachable> {
+    evaluator: E,
 }
 
 impl PushfourGame {
-    pub fn new(player: Player) -> PushfourGame {
+    pub fn new() -> PushfourGame {
+        PushfourGame::with_evaluator(ScoreReachable)
+    }
+}
+
+impl<E: Evaluator> PushfourGame<E> {
+    pub fn with_evaluator(evaluator: E) -> PushfourGame<E> {
         PushfourGame {
-            player: player,
+            evaluator: evaluator,
         }
     }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
 }
 
-impl Game<Board, Move> for PushfourGame {
+impl<E: Evaluator> Game<Board, Move> for PushfourGame<E> {
     fn get_moves(&self, root: &Board) -> Vec<Move> {
         root.get_moves()
     }
 
-    fn eval(&self, b: &Board, _: bool) -> i32 {
-        b.score_reachable(self.player)
+    fn eval(&self, b: &Board) -> i32 {
+        self.evaluator.eval(b, b.turn())
+    }
+
+    // Normally only the player who just moved can have won, but a position that was set up may
+    // have a win for either player
+    fn outcome(&self, b: &Board) -> Option<Outcome> {
+        if b.is_win_state(b.turn().other()) { return Some(Outcome::Loss); }
+        if b.is_win_state(b.turn()) { return Some(Outcome::Win); }
+        if b.legal_move_count() == 0 { return Some(Outcome::Draw); }
+        None
+    }
+
+    fn hash_key(&self, b: &Board) -> Option<u64> {
+        Some(b.hash())
+    }
+
+    // Central cells are part of more lines than those near the edges
+    fn order_score(&self, b: &Board, m: &Move) -> i32 {
+        let dist = |x: usize, size: usize| (2 * x as i32 - (size as i32 - 1)).abs();
+        -(dist(m.row, b.height()) + dist(m.col, b.width()))
+    }
+
+    fn move_index(&self, m: &Move) -> Option<usize> {
+        let player = if m.player == Player::Blue { 0 } else { 1 };
+        Some((m.row * MAX_BOARD_DIM + m.col) * 2 + player)
+    }
+
+    fn forcing_moves(&self, b: &Board) -> Vec<Move> {
+        b.forcing_moves()
+    }
+
+    // Every move fills a reachable cell, and cells that aren't reachable never become reachable
+    fn moves_left(&self, b: &Board) -> Option<u32> {
+        Some(b.reachable().count())
     }
 
     fn gameover(&self, b: &Board) -> bool {
@@ -44,8 +97,16 @@ impl Game<Board, Move> for PushfourGame {
 
     fn apply(&self, b: &Board, m: Move) -> Board {
         let mut cloned = b.clone();
-        cloned.set_move(m);
-        cloned.next_turn();
+        cloned.make_move(m);
         cloned
     }
+
+    fn make_move(&self, b: &mut Board, m: Move) -> Option<Board> {
+        b.make_move(m);
+        None
+    }
+
+    fn unmake_move(&self, b: &mut Board, m: Move, _: Option<Board>) {
+        b.unmake_move(m);
+    }
 }
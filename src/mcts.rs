@@ -0,0 +1,367 @@
+// Monte Carlo tree search, with UCT to pick which moves to explore: an alternative to Minimax that
+// needs no evaluation function and copes with wide trees by sampling instead of searching every
+// move. Works with any Game, scoring finished games by outcome() (or eval()'s sign if the game
+// doesn't provide outcome()).
+
+use std::f64;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use minimax::{Game, Outcome};
+
+// How rollouts pick their moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rollout {
+    // Uniformly at random
+    Random,
+    // A move that wins on the spot if there is one, else at random. Slower, but rollouts play
+    // less like neither side is trying.
+    WinFirst,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MctsOptions {
+    // The search stops after this many iterations or once `budget` runs out, whichever comes
+    // first. With neither set, it stops after the default number of iterations.
+    pub iterations: Option<u64>,
+    pub budget: Option<Duration>,
+    // UCT exploration constant: the higher, the more the search tries moves that have done badly
+    // so far
+    pub exploration: f64,
+    // Rollouts are random, but the same for a given seed
+    pub seed: u64,
+    pub rollout: Rollout,
+    // Rollouts still going after this many moves are scored by eval()
+    pub max_rollout: usize,
+}
+
+impl Default for MctsOptions {
+    fn default() -> MctsOptions {
+        MctsOptions {
+            iterations: Some(10000),
+            budget: None,
+            exploration: f64::consts::SQRT_2,
+            seed: 0,
+            rollout: Rollout::Random,
+            max_rollout: 1000,
+        }
+    }
+}
+
+// Outcome of a search: the most visited move, and how often each of the root's moves was visited
+// and how well it did
+#[derive(Clone, Debug)]
+pub struct MctsResult<Move> {
+    pub best_move: Move,
+    // (move, visits, average result for the player to move at the root, from 0 for a loss to 1
+    // for a win), in the order the moves were first tried
+    pub visits: Vec<(Move, u64, f64)>,
+    pub iterations: u64,
+    pub elapsed: Duration,
+}
+
+impl<Move: Copy> MctsResult<Move> {
+    // Average result of the best move
+    pub fn value(&self) -> f64 {
+        self.visits.iter().max_by_key(|&&(_, n, _)| n).map(|&(_, _, v)| v).unwrap_or(0.5)
+    }
+}
+
+struct Node<Move> {
+    mv: Option<Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // Moves without a child node yet
+    untried: Vec<Move>,
+    visits: u64,
+    // Total result for the player who made `mv`
+    wins: f64,
+}
+
+pub struct Mcts {
+    options: MctsOptions,
+}
+
+impl Mcts {
+    pub fn new() -> Mcts {
+        Mcts { options: MctsOptions::default() }
+    }
+
+    pub fn options(&self) -> MctsOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: MctsOptions) {
+        self.options = options;
+    }
+
+    // Searches `root` for the player to move there. Returns None if the game is already over,
+    // as there's no move to make.
+    pub fn search<State, Move, GameType>(&self, game: &GameType,
+                                         root: &State) -> Option<MctsResult<Move>>
+        where State: Clone + Debug,
+              Move: Copy + Debug,
+              GameType: Game<State, Move> {
+        if is_terminal(game, root) { return None; }
+        let start = Instant::now();
+        let deadline = self.options.budget.map(|b| start + b);
+        let limit = match (self.options.iterations, deadline) {
+            (None, None) => MctsOptions::default().iterations,
+            (n, _) => n,
+        };
+        let mut rng: XorShiftRng = SeedableRng::from_seed([
+            self.options.seed as u32, (self.options.seed >> 32) as u32, 0x6a09_e667, 0xbb67_ae85]);
+        let mut tree = vec![Node {
+            mv: None,
+            parent: None,
+            children: Vec::new(),
+            untried: game.get_moves(root),
+            visits: 0,
+            wins: 0.0,
+        }];
+
+        let mut iterations = 0;
+        loop {
+            if let Some(n) = limit {
+                if iterations >= n { break; }
+            }
+            if let Some(t) = deadline {
+                // Always finish one iteration, so there's a move to return
+                if iterations > 0 && Instant::now() >= t { break; }
+            }
+            iterations += 1;
+
+            // Selection: follow the best UCT child down to a node with untried moves or none at
+            // all
+            let mut state = root.clone();
+            let mut node = 0;
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                node = self.select(&tree, node);
+                game.make_move(&mut state, tree[node].mv.unwrap());
+            }
+
+            // Expansion: add one of the untried moves
+            if !tree[node].untried.is_empty() {
+                let i = rng.gen_range(0, tree[node].untried.len());
+                let mv = tree[node].untried.swap_remove(i);
+                game.make_move(&mut state, mv);
+                let child = tree.len();
+                tree.push(Node {
+                    mv: Some(mv),
+                    parent: Some(node),
+                    children: Vec::new(),
+                    untried: if is_terminal(game, &state) { Vec::new() }
+                             else { game.get_moves(&state) },
+                    visits: 0,
+                    wins: 0.0,
+                });
+                tree[node].children.push(child);
+                node = child;
+            }
+
+            // Simulation, then backpropagation. Each node's result is for the player who moved
+            // into it, whose opponent is the one to move there.
+            let mut result = 1.0 - self.rollout(game, &mut state, &mut rng);
+            let mut cur = Some(node);
+            while let Some(n) = cur {
+                tree[n].visits += 1;
+                tree[n].wins += result;
+                result = 1.0 - result;
+                cur = tree[n].parent;
+            }
+        }
+
+        let visits: Vec<(Move, u64, f64)> = tree[0].children.iter().map(|&c| {
+            let n = &tree[c];
+            (n.mv.unwrap(), n.visits, n.wins / n.visits as f64)
+        }).collect();
+        let best_move = visits.iter().max_by_key(|&&(_, n, _)| n).unwrap().0;
+        Some(MctsResult {
+            best_move: best_move,
+            visits: visits,
+            iterations: iterations,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    // The child of `node` with the highest upper confidence bound
+    fn select<Move>(&self, tree: &[Node<Move>], node: usize) -> usize {
+        let ln_n = (tree[node].visits as f64).ln();
+        let mut best = tree[node].children[0];
+        let mut best_ucb = f64::NEG_INFINITY;
+        for &c in tree[node].children.iter() {
+            let n = tree[c].visits as f64;
+            let ucb = tree[c].wins / n + self.options.exploration * (ln_n / n).sqrt();
+            if ucb > best_ucb {
+                best = c;
+                best_ucb = ucb;
+            }
+        }
+        best
+    }
+
+    // Plays `state` out, and returns the result for the player to move in it: 1 for a win, 0 for
+    // a loss and 0.5 for a draw
+    fn rollout<State, Move, GameType>(&self, game: &GameType, state: &mut State,
+                                      rng: &mut XorShiftRng) -> f64
+        where State: Clone + Debug,
+              Move: Copy,
+              GameType: Game<State, Move> {
+        // Flips each move, to keep the result for the player to move at the start
+        let mut flip = false;
+        for _ in 0..self.options.max_rollout {
+            if let Some(r) = terminal_result(game, state) {
+                return if flip { 1.0 - r } else { r };
+            }
+            let moves = game.get_moves(state);
+            let mv = match self.options.rollout {
+                Rollout::Random => moves[rng.gen_range(0, moves.len())],
+                Rollout::WinFirst => match winning_move(game, state, &moves) {
+                    Some(mv) => mv,
+                    None => moves[rng.gen_range(0, moves.len())],
+                },
+            };
+            game.make_move(state, mv);
+            flip = !flip;
+        }
+        let r = eval_result(game, state);
+        if flip { 1.0 - r } else { r }
+    }
+}
+
+// Result for the player to move, if the game is over
+fn terminal_result<State, Move, GameType>(game: &GameType, state: &State) -> Option<f64>
+    where State: Clone + Debug,
+          Move: Copy,
+          GameType: Game<State, Move> {
+    match game.outcome(state) {
+        Some(Outcome::Win) => Some(1.0),
+        Some(Outcome::Loss) => Some(0.0),
+        Some(Outcome::Draw) => Some(0.5),
+        None => if game.gameover(state) || game.get_moves(state).is_empty() {
+            Some(eval_result(game, state))
+        } else {
+            None
+        },
+    }
+}
+
+fn is_terminal<State, Move, GameType>(game: &GameType, state: &State) -> bool
+    where State: Clone + Debug,
+          Move: Copy,
+          GameType: Game<State, Move> {
+    terminal_result(game, state).is_some()
+}
+
+// eval()'s verdict, as a result: whoever it favours wins
+fn eval_result<State, Move, GameType>(game: &GameType, state: &State) -> f64
+    where State: Clone + Debug,
+          Move: Copy,
+          GameType: Game<State, Move> {
+    let score = game.eval(state);
+    if score > 0 { 1.0 } else if score < 0 { 0.0 } else { 0.5 }
+}
+
+// A move among `moves` that wins on the spot for the player to move
+fn winning_move<State, Move, GameType>(game: &GameType, state: &mut State,
+                                       moves: &[Move]) -> Option<Move>
+    where State: Clone + Debug,
+          Move: Copy,
+          GameType: Game<State, Move> {
+    for &mv in moves.iter() {
+        let undo = game.make_move(state, mv);
+        let wins = game.outcome(state) == Some(Outcome::Loss);
+        game.unmake_move(state, mv, undo);
+        if wins { return Some(mv); }
+    }
+    None
+}
+
+#[test]
+fn test_takes_the_win() {
+    use std::str::FromStr;
+    use board::Board;
+    use util::{Move, Player};
+    use PushfourGame;
+    // Blue wins by pushing in at 0:3
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 b b b - -
+                             1 r - - - -
+                             2 r - - # -
+                             3 r - - - -
+                             4 - - - - -
+                             Turn: Player Blue").unwrap();
+    let g = PushfourGame::new();
+    for &rollout in [Rollout::Random, Rollout::WinFirst].iter() {
+        let mut engine = Mcts::new();
+        engine.set_options(MctsOptions { iterations: Some(2000), rollout: rollout,
+                                         ..MctsOptions::default() });
+        let result = engine.search(&g, &b).unwrap();
+        assert_eq!(Move { row: 0, col: 3, player: Player::Blue }, result.best_move);
+        assert!(result.value() > 0.9);
+        assert_eq!(2000, result.iterations);
+        assert_eq!(2000, result.visits.iter().map(|&(_, n, _)| n).fold(0, |a, n| a + n));
+        assert_eq!(b.legal_move_count() as usize, result.visits.len());
+    }
+}
+
+#[test]
+fn test_seeded() {
+    use board::Board;
+    use PushfourGame;
+    let b = Board::new(5);
+    let g = PushfourGame::new();
+    let mut engine = Mcts::new();
+    engine.set_options(MctsOptions { iterations: Some(500), seed: 7, ..MctsOptions::default() });
+    let visits = |e: &Mcts| -> Vec<u64> {
+        e.search(&g, &b).unwrap().visits.iter().map(|&(_, n, _)| n).collect()
+    };
+    let first = visits(&engine);
+    assert_eq!(first, visits(&engine));
+    engine.set_options(MctsOptions { iterations: Some(500), seed: 8, ..MctsOptions::default() });
+    assert!(first != visits(&engine));
+}
+
+#[test]
+fn test_budget() {
+    use board::Board;
+    use PushfourGame;
+    let b = Board::new(5);
+    let g = PushfourGame::new();
+    let mut engine = Mcts::new();
+    engine.set_options(MctsOptions { iterations: None, budget: Some(Duration::from_millis(0)),
+                                     ..MctsOptions::default() });
+    let result = engine.search(&g, &b).unwrap();
+    assert_eq!(1, result.iterations);
+    assert!(b.get_moves_set().contains(&result.best_move));
+}
+
+#[test]
+fn test_no_limit() {
+    use board::Board;
+    use PushfourGame;
+    let b = Board::new(4);
+    let g = PushfourGame::new();
+    let mut engine = Mcts::new();
+    engine.set_options(MctsOptions { iterations: None, budget: None, ..MctsOptions::default() });
+    let result = engine.search(&g, &b).unwrap();
+    assert_eq!(MctsOptions::default().iterations, Some(result.iterations));
+}
+
+#[test]
+fn test_finished_root() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let won = Board::from_str("+ 0 1 2 3
+                               0 b b b b
+                               1 r r r -
+                               2 - - - -
+                               3 - - - -").unwrap();
+    let full = Board::from_str("+ 0 1
+                                0 b r
+                                1 r b").unwrap();
+    assert!(Mcts::new().search(&g, &won).is_none());
+    assert!(Mcts::new().search(&g, &full).is_none());
+}
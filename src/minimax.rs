@@ -5,104 +5,1304 @@
  */
 
 use std::cmp;
+use std::fmt;
+use std::mem;
+use std::thread;
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::time::{Duration, Instant};
+use transposition::{Bound, TableStats, TranspositionTable};
+
+// Iterative deepening gives up after this many plies even if time remains
+pub const MAX_ITERATIVE_DEPTH: i32 = 64;
+
+// Number of transposition table entries used when no size is given
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 18;
+
+// Score of a won position. A win found N plies from the root scores WIN_SCORE - N (and a loss
+// -(WIN_SCORE - N)), so faster wins and slower losses score better. Evaluations must stay below
+// WIN_SCORE - MAX_PLY.
+pub const WIN_SCORE: i32 = 1_000_000;
+
+// Deepest ply from the root that mate-distance scores allow for
+pub const MAX_PLY: i32 = 1000;
+
+// How a finished game ended, for the player to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+// A result proven by the search: the player to move at the root wins (or loses) with the Nth
+// ply from the root at best (or at worst), or, when the endgame solver searched to the end of
+// the game, can't do better than a draw
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proven {
+    Win(i32),
+    Loss(i32),
+    Draw,
+}
+
+impl fmt::Display for Proven {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Proven::Win(n) => write!(f, "win in {}", n),
+            Proven::Loss(n) => write!(f, "loss in {}", n),
+            Proven::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+// Whether `score` is a proven win or loss rather than an evaluation
+pub fn proven(score: i32) -> Option<Proven> {
+    if score >= WIN_SCORE - MAX_PLY { return Some(Proven::Win(WIN_SCORE - score)); }
+    if score <= -(WIN_SCORE - MAX_PLY) { return Some(Proven::Loss(WIN_SCORE + score)); }
+    None
+}
+
+// Mate scores are stored in the table relative to the node they were found at, not the root,
+// so they stay right when the position comes up again at another ply
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    match proven(score) {
+        Some(Proven::Win(_)) => score + ply,
+        Some(Proven::Loss(_)) => score - ply,
+        _ => score,
+    }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    match proven(score) {
+        Some(Proven::Win(_)) => score - ply,
+        Some(Proven::Loss(_)) => score + ply,
+        _ => score,
+    }
+}
 
 pub trait Game<State: Clone + Debug, Move: Copy> {
     fn get_moves(&self, &State) -> Vec<Move>;
-    fn eval(&self, &State, my_turn: bool) -> i32;
+    // Score of the state for the player whose turn it is: the better for them, the higher
+    fn eval(&self, &State) -> i32;
     fn apply(&self, &State, Move) -> State;
     fn gameover(&self, &State) -> bool;
+
+    // In-place counterpart of apply(), which is what the search uses: it makes the move on
+    // `state` and returns whatever unmake_move() needs to take it back. The defaults go through
+    // apply() and hand back the replaced State; games whose moves are cheap to reverse should
+    // override both and avoid the copy.
+    fn make_move(&self, state: &mut State, mv: Move) -> Option<State> {
+        let next = self.apply(state, mv);
+        Some(mem::replace(state, next))
+    }
+
+    fn unmake_move(&self, state: &mut State, _mv: Move, undo: Option<State>) {
+        if let Some(prev) = undo { *state = prev; }
+    }
+
+    // How the game ended, if it's over. Finished games are scored by this rather than eval(), with
+    // wins and losses adjusted for how far from the root they are. The default leaves everything
+    // to eval().
+    fn outcome(&self, _: &State) -> Option<Outcome> { None }
+
+    // Move ordering hints. The search tries the best moves first, since the sooner it finds a
+    // good move, the more of the rest it can prune. order_score() is a cheap static guess at how
+    // good a move is (higher is better), and move_index() gives each move a small number to
+    // keep track of its history under. The defaults provide neither.
+    fn order_score(&self, _: &State, _: &Move) -> i32 { 0 }
+
+    fn move_index(&self, _: &Move) -> Option<usize> { None }
+
+    // Moves too urgent to leave until after the search's horizon, like winning moves and blocks
+    // of the opponent's winning moves. With SearchOptions::extension, the search goes on past
+    // its depth through these alone. Wins and losses found that way count as proven, so a
+    // position's forcing moves must include every move that could stave off a loss. The default
+    // has none.
+    fn forcing_moves(&self, _: &State) -> Vec<Move> { Vec::new() }
+
+    // At most how many more moves the game can last, if that's known. It lets the endgame
+    // solver (SearchOptions::endgame) search small enough endgames to the end.
+    fn moves_left(&self, _: &State) -> Option<u32> { None }
+
+    // Key identifying `state` in the transposition table. Games that don't provide one are
+    // searched without a table.
+    fn hash_key(&self, _: &State) -> Option<u64> { None }
+}
+
+// Counters for the work a search did
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+    // Positions visited, leaves included
+    pub nodes: u64,
+    // Calls to Game::eval()
+    pub leaf_evals: u64,
+    // Alpha-beta cutoffs, by ply from the root
+    pub cutoffs: Vec<u64>,
+    // Moves searched again with a full window after a principal variation search null-window
+    // search showed they might be better
+    pub pvs_researches: u64,
+    // Iterations searched again with a full window after falling outside the aspiration window
+    pub aspiration_researches: u64,
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    pub fn nodes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64 + self.elapsed.subsec_nanos() as f64 * 1e-9;
+        if secs == 0.0 { 0.0 } else { self.nodes as f64 / secs }
+    }
+
+    fn record_cutoff(&mut self, ply: i32) {
+        let ply = ply as usize;
+        if self.cutoffs.len() <= ply { self.cutoffs.resize(ply + 1, 0); }
+        self.cutoffs[ply] += 1;
+    }
 }
 
-pub struct Minimax;
-impl Minimax {
-    pub fn best_move<State, Move, GameType>(depth: i32, game: &GameType, root: &State) -> Move
+// Search algorithm variations. The defaults are plain alpha-beta with full windows, on one
+// thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    // Principal variation search: only the first move at each node gets the full window. The
+    // rest are searched with a null window, which only shows whether they're better than the
+    // best so far, and searched again properly if they are.
+    pub pvs: bool,
+    // Iterative deepening searches each depth with a window this far either side of the previous
+    // depth's score, searching again with a full window if the score falls outside it
+    pub aspiration: Option<i32>,
+    // Threads used by search_parallel()
+    pub threads: usize,
+    // Plies past the search depth to keep searching Game::forcing_moves() for, so the search
+    // doesn't miss a win or loss just over its horizon. 0 turns this off.
+    pub extension: i32,
+    // Endgame solver: positions with fewer than this many moves left (by Game::moves_left()) are
+    // searched to the end of the game, however deep that is, without relying on eval(). 0 turns
+    // this off.
+    pub endgame: u32,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            pvs: false,
+            aspiration: None,
+            threads: 1,
+            extension: 0,
+            endgame: 0,
+        }
+    }
+}
+
+// Outcome of a search: the chosen move, its score (for the player to move at the root), the
+// depth that produced it and the principal variation, i.e. the line of play both players are
+// expected to follow, starting with `best_move`. The variation stops short wherever the
+// transposition table settled a position without a search. Stats cover the whole search,
+// including every iteration of iterative deepening.
+#[derive(Clone, Debug)]
+pub struct SearchResult<Move> {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: i32,
+    pub pv: Vec<Move>,
+    pub stats: SearchStats,
+    // Whether a StopHandle cut the search short. A stopped fixed-depth search only searched some
+    // of the root's moves to `depth`, and its best move is the best of those.
+    pub stopped: bool,
+    // Whether the endgame solver searched every line to the end of the game, making the score
+    // exact: a win, a loss or (if it's neither) a draw
+    pub solved: bool,
+}
+
+impl<Move> SearchResult<Move> {
+    // The forced win, loss or draw, if the search proved one
+    pub fn proven(&self) -> Option<Proven> {
+        match proven(self.score) {
+            None if self.solved => Some(Proven::Draw),
+            p => p,
+        }
+    }
+}
+
+// Shared flag for stopping searches from another thread. Once stopped, it stops every search
+// using it, including ones started later, until it's reset.
+#[derive(Clone, Debug, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn new() -> StopHandle {
+        StopHandle::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Progress updates from a search, e.g. for showing what the engine is thinking while it thinks
+pub trait SearchObserver<Move> {
+    // Called with the result of each iteration of iterative deepening as it completes, and of
+    // each fixed-depth search. Stats and elapsed time are for the whole search so far.
+    fn iteration(&mut self, result: &SearchResult<Move>);
+}
+
+// The search engine. It keeps its transposition table between searches, so searching successive
+// positions of the same game with one instance reuses earlier work.
+pub struct Minimax<Move> {
+    tt: TranspositionTable<Move>,
+    deadline: Option<Instant>,
+    stats: SearchStats,
+    // pv[ply] is the best line found from the node being searched at `ply`
+    pv: Vec<Vec<Move>>,
+    // Killer moves: the last two moves that caused a cutoff at each ply. A move that refuted one
+    // position is likely to refute its siblings too.
+    killers: Vec<[Option<Move>; 2]>,
+    // History heuristic: how much pruning each move (by Game::move_index) has caused anywhere in
+    // the tree, weighted towards cutoffs far from the leaves
+    history: Vec<u64>,
+    options: SearchOptions,
+    // Engines for the other threads of a parallel search, kept so their tables carry over
+    workers: Vec<Minimax<Move>>,
+    stop: StopHandle,
+    observer: Option<Box<SearchObserver<Move> + Send>>,
+    // Best root move so far in the current iteration, and its score
+    root_best: Option<(Move, i32)>,
+}
+
+impl<Move: Copy + PartialEq> Minimax<Move> {
+    pub fn new() -> Minimax<Move> {
+        Minimax::with_table_size(DEFAULT_TABLE_SIZE)
+    }
+
+    pub fn with_table_size(size: usize) -> Minimax<Move> {
+        Minimax {
+            tt: TranspositionTable::new(size),
+            deadline: None,
+            stats: SearchStats::default(),
+            pv: Vec::new(),
+            killers: Vec::new(),
+            history: Vec::new(),
+            options: SearchOptions::default(),
+            workers: Vec::new(),
+            stop: StopHandle::new(),
+            observer: None,
+            root_best: None,
+        }
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    // Handle that stops this engine's searches. Stopped searches still return the best move
+    // they've found.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    pub fn set_stop_handle(&mut self, stop: StopHandle) {
+        self.stop = stop;
+    }
+
+    pub fn set_observer(&mut self, observer: Option<Box<SearchObserver<Move> + Send>>) {
+        self.observer = observer;
+    }
+
+    // Stats for the engine's table, together with its parallel search workers' tables
+    pub fn table_stats(&self) -> TableStats {
+        let mut stats = self.tt.stats();
+        for w in self.workers.iter() {
+            let s = w.table_stats();
+            stats.hits += s.hits;
+            stats.misses += s.misses;
+            stats.stores += s.stores;
+            stats.cutoffs += s.cutoffs;
+        }
+        stats
+    }
+
+    pub fn clear_table(&mut self) {
+        self.tt.clear();
+        for w in self.workers.iter_mut() { w.clear_table(); }
+    }
+
+    // One-shot search with a fresh engine
+    pub fn best_move<State, GameType>(depth: i32, game: &GameType, root: &State) -> Move
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        Minimax::new().search(depth, game, root).best_move
+    }
+
+    // One-shot timed search with a fresh engine; see search_timed()
+    pub fn best_move_timed<State, GameType>(budget: Duration, game: &GameType,
+                                            root: &State) -> SearchResult<Move>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        Minimax::new().search_timed(budget, game, root)
+    }
+
+    pub fn search<State, GameType>(&mut self, depth: i32, game: &GameType,
+                                   root: &State) -> SearchResult<Move>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let start = Instant::now();
+        self.new_search();
+        let (depth, solving) = self.endgame_depth(depth, game, root);
+        let mut result = match self.search_root(depth, game, root, -i32::max_value(),
+                                                i32::max_value()) {
+            Some(r) => r,
+            None => self.stopped_result(depth, game, root),
+        };
+        result.solved = solving && !result.stopped;
+        result.stats.elapsed = start.elapsed();
+        if !result.stopped { self.notify(&result); }
+        result
+    }
+
+    // Iterative deepening: searches depth 1, 2, 3... until `budget` runs out, and returns the
+    // best move of the deepest iteration that completed. Depth 1 always runs to completion, so
+    // a tiny budget still yields a legal move.
+    pub fn search_timed<State, GameType>(&mut self, budget: Duration, game: &GameType,
+                                         root: &State) -> SearchResult<Move>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        self.deepen(Some(Instant::now() + budget), game, root)
+    }
+
+    // Iterative deepening with no time limit, until the stop handle stops it (or it reaches the
+    // maximum depth, or proves a result)
+    pub fn search_until_stopped<State, GameType>(&mut self, game: &GameType,
+                                                 root: &State) -> SearchResult<Move>
         where State: Clone + Debug,
-              Move: Copy,
               GameType: Game<State, Move> {
-        let (a, b) = (i32::min_value(), i32::max_value());
-        let (mv, _score) = Minimax::min_max(depth, game, root, false, a, b);
-        mv.expect("no moves")
+        self.deepen(None, game, root)
     }
 
-    fn min_max<State, Move, GameType>(depth: i32, game: &GameType, root: &State, do_min: bool,
-                                      mut a: i32, mut b: i32) -> (Option<Move>, i32)
+    fn deepen<State, GameType>(&mut self, deadline: Option<Instant>, game: &GameType,
+                               root: &State) -> SearchResult<Move>
         where State: Clone + Debug,
-              Move: Copy,
               GameType: Game<State, Move> {
+        let start = Instant::now();
+        self.new_search();
+        self.deadline = deadline;
+        // The first iteration is depth 1, or the whole game if the endgame solver takes it on. A
+        // solve that runs out of time or is stopped gives way to a depth 1 search, which always
+        // runs to completion, so there's still a legal move.
+        let (first, solving) = self.endgame_depth(1, game, root);
+        let mut result = match self.search_root(first, game, root, -i32::max_value(),
+                                                i32::max_value()) {
+            Some(mut r) => {
+                r.solved = solving;
+                r
+            }
+            None => self.search_depth_one(game, root),
+        };
+        result.stats.elapsed = start.elapsed();
+        self.notify(&result);
+        let mut depth = result.depth + 1;
+        // Searching deeper can't find a faster win or a slower loss than a proven one
+        while depth <= MAX_ITERATIVE_DEPTH && !self.out_of_time() &&
+              result.proven().is_none() && !result.stopped {
+            match self.search_iteration(depth, game, root, result.score) {
+                Some(mut r) => {
+                    r.stats.elapsed = start.elapsed();
+                    self.notify(&r);
+                    result = r;
+                }
+                None => break,
+            }
+            depth += 1;
+        }
+        result.stopped |= self.stop.is_stopped();
+        self.deadline = None;
+        result.stats = self.stats.clone();
+        result.stats.elapsed = start.elapsed();
+        result
+    }
 
-        if depth == 5 {
-            //println!("\nmin_max: {:?}\n             depth {} do_min {} a {} b {}\n",
-            //         root, depth, do_min, a, b);
+    // Multi-PV search: the best `k` moves at the root (or all of them, if there are fewer), best
+    // first, each with its exact score and principal variation. Moves with the same score keep
+    // the order they were searched in. Stats are for the whole search, and shared by all the
+    // results. If the stop handle stops the search, only the moves searched so far are ranked.
+    pub fn search_multipv<State, GameType>(&mut self, depth: i32, k: usize, game: &GameType,
+                                           root: &State) -> Vec<SearchResult<Move>>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let mut moves = game.get_moves(root);
+        if k <= 1 || depth <= 0 || game.outcome(root).is_some() || game.gameover(root) {
+            return vec![self.search(depth, game, root)];
         }
-        // Don't need to do anything if depth is 0, someone has won, or there are no moves
-        if depth == 0 {
-            let score = game.eval(root, do_min);
-            //println!("depth is 0, returning {}", score);
-            return (None, score);
+        let start = Instant::now();
+        self.new_search();
+        self.root_best = None;
+        let (depth, solving) = self.endgame_depth(depth, game, root);
+        let hash_mv = game.hash_key(root)
+            .and_then(|k| self.tt.probe(k)).and_then(|e| e.best_move);
+        self.order_moves(game, root, &mut moves, hash_mv, 0);
+        self.stats.nodes += 1;
+
+        // Like search_parallel(), but keeping the best k: once there are k exact scores, a move
+        // has to beat the lowest of them to make the list, so it's searched with alpha just below
+        // that. Each list is kept sorted, best first.
+        let mut lines: Vec<(i32, Move, Vec<Move>)> = Vec::new();
+        let mut stopped = false;
+        let mut state = root.clone();
+        for &mv in moves.iter() {
+            let a = if lines.len() >= k { lines[k - 1].0 - 1 } else { -i32::max_value() };
+            let undo = game.make_move(&mut state, mv);
+            let child = self.negamax(depth - 1, 1, game, &mut state, -i32::max_value(), -a);
+            game.unmake_move(&mut state, mv, undo);
+            let v = match child {
+                Some((_, v)) => -v,
+                None => {
+                    stopped = true;
+                    break;
+                }
+            };
+            if v > a {
+                let mut pv = vec![mv];
+                pv.extend_from_slice(&self.pv[1]);
+                let i = lines.iter().position(|l| l.0 < v).unwrap_or(lines.len());
+                lines.insert(i, (v, mv, pv));
+                lines.truncate(k);
+            }
+        }
+        if lines.is_empty() {
+            let mut result = self.stopped_result(depth, game, root);
+            result.stats.elapsed = start.elapsed();
+            return vec![result];
         }
-        if game.gameover(root) {
-            let score = game.eval(root, do_min);
-            //println!("gameover, returning {}", score);
-            return (None, score);
+        if !stopped {
+            if let Some(k) = game.hash_key(root) {
+                self.tt.store(k, depth, score_to_table(lines[0].0, 0), Bound::Exact,
+                              Some(lines[0].1));
+            }
         }
-        let moves = game.get_moves(root);
+        self.stats.elapsed = start.elapsed();
+        lines.into_iter().map(|(score, mv, pv)| SearchResult {
+            best_move: mv,
+            score: score,
+            depth: depth,
+            pv: pv,
+            stats: self.stats.clone(),
+            stopped: stopped,
+            solved: solving && !stopped,
+        }).collect()
+    }
+
+    // Best move of a search to `depth` that the stop handle stopped: the best of the root moves
+    // searched before it stopped, or a depth 1 search's if it didn't get that far
+    fn stopped_result<State, GameType>(&mut self, depth: i32, game: &GameType,
+                                       root: &State) -> SearchResult<Move>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let mut result = match self.root_best {
+            Some((mv, score)) => SearchResult {
+                best_move: mv,
+                score: score,
+                depth: depth,
+                pv: self.pv[0].clone(),
+                stats: self.stats.clone(),
+                stopped: true,
+                solved: false,
+            },
+            None => self.search_depth_one(game, root),
+        };
+        result.stopped = true;
+        result
+    }
+
+    // A depth 1 search that neither the stop handle nor the deadline can interrupt
+    fn search_depth_one<State, GameType>(&mut self, game: &GameType,
+                                         root: &State) -> SearchResult<Move>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let stop = mem::replace(&mut self.stop, StopHandle::new());
+        let deadline = self.deadline.take();
+        let r = self.search_root(1, game, root, -i32::max_value(), i32::max_value());
+        self.stop = stop;
+        self.deadline = deadline;
+        r.expect("search without a deadline can't time out")
+    }
+
+    // The depth to search `root` to: `depth`, unless the endgame solver is on and the game is
+    // close enough to its end to search to the end. Also returns whether that's the case.
+    fn endgame_depth<State, GameType>(&self, depth: i32, game: &GameType,
+                                      root: &State) -> (i32, bool)
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        match game.moves_left(root) {
+            Some(n) if n < self.options.endgame => (cmp::max(depth, n as i32), true),
+            _ => (depth, false),
+        }
+    }
+
+    fn notify(&mut self, result: &SearchResult<Move>) {
+        if let Some(ref mut o) = self.observer { o.iteration(result); }
+    }
+
+    fn out_of_time(&self) -> bool {
+        if self.stop.is_stopped() { return true; }
+        match self.deadline {
+            Some(t) => Instant::now() >= t,
+            None => false,
+        }
+    }
+
+    // Resets everything that shouldn't carry over from the last search
+    fn new_search(&mut self) {
+        self.stats = SearchStats::default();
+        self.deadline = None;
+        // Killers only make sense for the position they were found in, but history carries over
+        // to the next search, at half weight
+        self.killers.clear();
+        for h in self.history.iter_mut() { *h /= 2; }
+    }
+
+    // One iteration of iterative deepening, within the aspiration window around `prev_score` if
+    // there is one
+    fn search_iteration<State, GameType>(&mut self, depth: i32, game: &GameType, root: &State,
+                                         prev_score: i32) -> Option<SearchResult<Move>>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let (min, max) = (-i32::max_value(), i32::max_value());
+        if let Some(w) = self.options.aspiration {
+            if proven(prev_score).is_none() {
+                let (a, b) = (cmp::max(min, prev_score - w), cmp::min(max, prev_score + w));
+                let result = match self.search_root(depth, game, root, a, b) {
+                    Some(r) => r,
+                    None => return None,
+                };
+                if result.score > a && result.score < b { return Some(result); }
+                self.stats.aspiration_researches += 1;
+            }
+        }
+        self.search_root(depth, game, root, min, max)
+    }
+
+    // Searches `root` to `depth` within the window (a, b), adding to the stats. Returns None if
+    // the deadline passes.
+    fn search_root<State, GameType>(&mut self, depth: i32, game: &GameType, root: &State,
+                                    a: i32, b: i32) -> Option<SearchResult<Move>>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let mut state = root.clone();
+        self.root_best = None;
+        let (mv, score) = match self.negamax(depth, 0, game, &mut state, a, b) {
+            Some(r) => r,
+            None => return None,
+        };
+        Some(SearchResult {
+            best_move: mv.expect("no moves"),
+            score: score,
+            depth: depth,
+            pv: self.pv[0].clone(),
+            stats: self.stats.clone(),
+            stopped: false,
+            solved: false,
+        })
+    }
+
+    // Sorts `moves` best first: the hash move, then this ply's killers, then by history and
+    // finally by the game's static order score. Ties keep their original order.
+    fn order_moves<State, GameType>(&self, game: &GameType, state: &State, moves: &mut Vec<Move>,
+                                    hash_mv: Option<Move>, ply: i32)
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let killers = self.killers.get(ply as usize).cloned().unwrap_or([None, None]);
+        let mut keyed: Vec<((u8, u64, i32), Move)> = moves.iter().map(|&m| {
+            let rank = if Some(m) == hash_mv { 3 }
+                       else if Some(m) == killers[0] { 2 }
+                       else if Some(m) == killers[1] { 1 }
+                       else { 0 };
+            let history = game.move_index(&m)
+                .and_then(|i| self.history.get(i).cloned()).unwrap_or(0);
+            ((rank, history, game.order_score(state, &m)), m)
+        }).collect();
+        keyed.sort_by(|x, y| y.0.cmp(&x.0));
+        moves.clear();
+        moves.extend(keyed.into_iter().map(|(_, m)| m));
+    }
+
+    // Remembers that `mv` caused a cutoff, `depth` plies from the horizon
+    fn record_refutation<State, GameType>(&mut self, game: &GameType, mv: Move, ply: i32,
+                                          depth: i32)
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        let p = ply as usize;
+        if self.killers.len() <= p { self.killers.resize(p + 1, [None, None]); }
+        if self.killers[p][0] != Some(mv) {
+            self.killers[p][1] = self.killers[p][0];
+            self.killers[p][0] = Some(mv);
+        }
+        if let Some(i) = game.move_index(&mv) {
+            if self.history.len() <= i { self.history.resize(i + 1, 0); }
+            self.history[i] += (depth * depth) as u64;
+        }
+    }
+
+    // Negamax: scores `root` for the player to move there, as the best of its children's scores
+    // negated. Searches by making and unmaking moves on `root`; it's back to its original state
+    // when this returns. Returns None if the deadline passes before the search completes.
+    // `ply` is the distance from the root of the whole search.
+    fn negamax<State, GameType>(&mut self, depth: i32, ply: i32, game: &GameType,
+                                root: &mut State, mut a: i32, b: i32)
+                                -> Option<(Option<Move>, i32)>
+        where State: Clone + Debug,
+              GameType: Game<State, Move> {
+        self.stats.nodes += 1;
+        let p = ply as usize;
+        if self.pv.len() <= p { self.pv.resize(p + 1, Vec::new()); }
+        self.pv[p].clear();
+
+        // Don't need to do anything if the game is over, the search is out of depth, or there are
+        // no moves
+        match game.outcome(root) {
+            Some(Outcome::Win) => return Some((None, WIN_SCORE - ply)),
+            Some(Outcome::Loss) => return Some((None, -(WIN_SCORE - ply))),
+            Some(Outcome::Draw) => return Some((None, 0)),
+            None => {}
+        }
+        // Past the horizon, depth counts the plies of extension down from 0
+        if (depth <= 0 && -depth >= self.options.extension) || game.gameover(root) {
+            self.stats.leaf_evals += 1;
+            return Some((None, game.eval(root)));
+        }
+        if self.out_of_time() { return None; }
+
+        // A deep enough table entry may settle this node without searching it. Either way, its
+        // best move is the one most likely to cause a cutoff, so it gets searched first.
+        let key = game.hash_key(root);
+        let mut hash_mv = None;
+        if let Some(k) = key {
+            if let Some(e) = self.tt.probe(k) {
+                hash_mv = e.best_move;
+                let score = score_from_table(e.score, ply);
+                if ply > 0 && e.depth >= depth {
+                    let usable = match e.bound {
+                        Bound::Exact => true,
+                        Bound::Lower => score >= b,
+                        Bound::Upper => score <= a,
+                    };
+                    if usable {
+                        self.tt.record_cutoff();
+                        if let Some(mv) = e.best_move { self.pv[p].push(mv); }
+                        return Some((e.best_move, score));
+                    }
+                }
+            }
+        }
+
+        let mut moves = if depth > 0 { game.get_moves(root) } else { game.forcing_moves(root) };
         if moves.len() == 0 {
-            let score = game.eval(root, do_min);
-            //println!("no moves, returning {}", score);
-            return (None, score);
+            self.stats.leaf_evals += 1;
+            return Some((None, game.eval(root)));
         }
+        self.order_moves(game, root, &mut moves, hash_mv, ply);
 
+        let orig_a = a;
         let mut best_mv: Option<Move> = None;
-        let mut best_v: i32 = i32::min_value();
+        let mut best_v: i32 = -i32::max_value();
         for &mv in moves.iter() {
-            let child = game.apply(root, mv);
-            let (_child_mv, child_v) = Minimax::min_max(depth - 1, game, &child, !do_min, a, b);
-            if depth == 6 {
-                //println!("result {} from child {:?}", child_v, child);
+            let undo = game.make_move(root, mv);
+            let mut child_result;
+            if self.options.pvs && best_mv.is_some() {
+                child_result = self.negamax(depth - 1, ply + 1, game, root, -a - 1, -a);
+                if let Some((_, v)) = child_result {
+                    if -v > a && -v < b {
+                        self.stats.pvs_researches += 1;
+                        child_result = self.negamax(depth - 1, ply + 1, game, root, -b, -a);
+                    }
+                }
+            } else {
+                child_result = self.negamax(depth - 1, ply + 1, game, root, -b, -a);
             }
-            // If nothing else, just choose the first move
-            if best_mv.is_none() {
-                best_mv = Some(mv);
+            game.unmake_move(root, mv, undo);
+            let child_v = match child_result {
+                Some((_, v)) => -v,
+                None => return None,
+            };
+            // The first move is the best so far; after that, a move has to beat it
+            if best_mv.is_none() || child_v > best_v {
                 best_v = child_v;
-                continue;
+                best_mv = Some(mv);
+                let (line, rest) = self.pv.split_at_mut(p + 1);
+                line[p].clear();
+                line[p].push(mv);
+                line[p].extend_from_slice(&rest[0]);
+                if ply == 0 { self.root_best = Some((mv, best_v)); }
+            }
+            a = cmp::max(a, best_v);
+            if b <= a {
+                self.stats.record_cutoff(ply);
+                self.record_refutation(game, mv, ply, depth);
+                break;
             }
+        };
 
-            // Otherwise, apply alpha-beta pruning
-            if !do_min {
-                if child_v > best_v {
-                    best_v = child_v;
-                    best_mv = Some(mv);
-                }
-                a = cmp::max(a, best_v);
-                if b <= a {
-                    //println!("breaking for AB depth {} a {} b {}", depth, a, b);
-                    break;
+        if let Some(k) = key {
+            let bound = if best_v <= orig_a { Bound::Upper }
+                        else if best_v >= b { Bound::Lower }
+                        else { Bound::Exact };
+            self.tt.store(k, depth, score_to_table(best_v, ply), bound, best_mv);
+        }
+        Some((best_mv, best_v))
+    }
+}
+
+// Root-parallel search. The root's moves are dealt out to `options.threads` threads, each with
+// its own engine and table, and the best score found so far is shared between them so they can
+// prune against it.
+impl<Move: Copy + PartialEq + Send + 'static> Minimax<Move> {
+    // Searches `root` to `depth` like search(), on several threads. The score is always the
+    // same as search()'s, and so is the move, unless several moves share the best score.
+    pub fn search_parallel<State, GameType>(&mut self, depth: i32, game: &GameType,
+                                            root: &State) -> SearchResult<Move>
+        where State: Clone + Debug + Send + 'static,
+              GameType: Game<State, Move> + Clone + Send + 'static {
+        let threads = self.options.threads;
+        let mut moves = game.get_moves(root);
+        if threads <= 1 || depth <= 0 || moves.len() < 2 || game.outcome(root).is_some() ||
+           game.gameover(root) {
+            return self.search(depth, game, root);
+        }
+        let start = Instant::now();
+        self.new_search();
+        self.root_best = None;
+        let (depth, solving) = self.endgame_depth(depth, game, root);
+        let hash_mv = game.hash_key(root)
+            .and_then(|k| self.tt.probe(k)).and_then(|e| e.best_move);
+        self.order_moves(game, root, &mut moves, hash_mv, 0);
+
+        let size = cmp::max(1, self.tt.capacity() / threads);
+        while self.workers.len() < threads {
+            self.workers.push(Minimax::with_table_size(size));
+        }
+        // Scores at or below `best - 1` can't be best, so each move is searched with alpha just
+        // below the best score so far. A result above that alpha is exact, and the moves that
+        // tie for best all get one.
+        let best = Arc::new(AtomicIsize::new(-i32::max_value() as isize));
+        let mut handles = Vec::new();
+        for (t, mut worker) in self.workers.drain(..).take(threads).enumerate() {
+            let mine: Vec<(usize, Move)> = moves.iter().cloned().enumerate()
+                .filter(|&(i, _)| i % threads == t).collect();
+            let (game, mut state, best) = (game.clone(), root.clone(), best.clone());
+            worker.options = self.options;
+            worker.stop = self.stop.clone();
+            handles.push(thread::spawn(move || {
+                worker.new_search();
+                let mut results = Vec::new();
+                let mut stopped = false;
+                for (i, mv) in mine {
+                    let a = cmp::max(-i32::max_value(), best.load(Ordering::SeqCst) as i32 - 1);
+                    let undo = game.make_move(&mut state, mv);
+                    let child = worker.negamax(depth - 1, 1, &game, &mut state,
+                                               -i32::max_value(), -a);
+                    game.unmake_move(&mut state, mv, undo);
+                    let v = match child {
+                        Some((_, v)) => -v,
+                        None => {
+                            stopped = true;
+                            break;
+                        }
+                    };
+                    if v > a {
+                        let mut cur = best.load(Ordering::SeqCst);
+                        while (v as isize) > cur {
+                            match best.compare_exchange_weak(cur, v as isize, Ordering::SeqCst,
+                                                             Ordering::SeqCst) {
+                                Ok(_) => break,
+                                Err(actual) => cur = actual,
+                            }
+                        }
+                        let mut pv = vec![mv];
+                        pv.extend_from_slice(&worker.pv[1]);
+                        results.push((i, mv, v, pv));
+                    }
                 }
-            } else {
-                if child_v < best_v {
-                    best_v = child_v;
-                    best_mv = Some(mv);
+                (worker, results, stopped)
+            }));
+        }
+
+        // The best score, and the first move in search order to reach it, as search() would pick
+        let mut best_result: Option<(usize, Move, i32, Vec<Move>)> = None;
+        let mut stopped = false;
+        self.stats.nodes += 1;
+        for h in handles {
+            let (worker, results, s) = h.join().expect("search thread panicked");
+            stopped |= s;
+            self.add_stats(&worker.stats);
+            self.workers.push(worker);
+            for r in results {
+                let better = match best_result {
+                    Some((i, _, v, _)) => r.2 > v || (r.2 == v && r.0 < i),
+                    None => true,
+                };
+                if better { best_result = Some(r); }
+            }
+        }
+        // If the stop handle stopped the workers, some moves weren't searched, and the result
+        // is only the best of the rest
+        let mut result = match best_result {
+            Some((_, mv, score, pv)) => {
+                if !stopped {
+                    if let Some(k) = game.hash_key(root) {
+                        self.tt.store(k, depth, score_to_table(score, 0), Bound::Exact, Some(mv));
+                    }
                 }
-                b = cmp::min(b, best_v);
-                if b <= a {
-                    //println!("breaking for AB depth {} a {} b {}", depth, a, b);
-                    break;
+                SearchResult {
+                    best_move: mv,
+                    score: score,
+                    depth: depth,
+                    pv: pv,
+                    stats: self.stats.clone(),
+                    stopped: stopped,
+                    solved: solving && !stopped,
                 }
             }
-            if depth == 6 {
-                //println!("At depth {}, now a {} b {}", depth, a, b);
-            }
+            None => self.stopped_result(depth, game, root),
         };
+        result.stats.elapsed = start.elapsed();
+        if !result.stopped { self.notify(&result); }
+        result
+    }
 
-        best_mv.expect("no moves?");
-        if depth == 5 {
-            //println!("at depth {}, returning {} for: {:?}", depth, best_v, root);
+    fn add_stats(&mut self, other: &SearchStats) {
+        self.stats.nodes += other.nodes;
+        self.stats.leaf_evals += other.leaf_evals;
+        if self.stats.cutoffs.len() < other.cutoffs.len() {
+            self.stats.cutoffs.resize(other.cutoffs.len(), 0);
         }
-        (best_mv, best_v)
+        for (c, o) in self.stats.cutoffs.iter_mut().zip(other.cutoffs.iter()) { *c += *o; }
+        self.stats.pvs_researches += other.pvs_researches;
+        self.stats.aspiration_researches += other.aspiration_researches;
     }
 }
 
 #[test]
 fn it_works() {
 }
+
+#[test]
+fn test_best_move_timed_no_budget() {
+    use board::Board;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let b = Board::new(4);
+    let result = Minimax::best_move_timed(Duration::from_millis(0), &g, &b);
+    assert_eq!(1, result.depth);
+    assert!(b.get_moves_set().contains(&result.best_move));
+}
+
+#[cfg(test)]
+fn test_board() -> ::board::Board {
+    use std::str::FromStr;
+    ::board::Board::from_str("+ 0 1 2 3 4
+                              0 - - b - -
+                              1 - r - # -
+                              2 - - r b -
+                              3 - b - - -
+                              4 - - r - -").unwrap()
+}
+
+#[test]
+fn test_table_agrees_with_plain_search() {
+    use PushfourGame;
+    let b = test_board();
+    let g = PushfourGame::new();
+    let mut with_table = Minimax::new();
+    let mut without: Minimax<_> = Minimax::with_table_size(1);
+    let expected = without.search(4, &NoHash(&g), &b).score;
+    assert_eq!(expected, with_table.search(4, &g, &b).score);
+    assert!(with_table.table_stats().hits > 0);
+}
+
+#[test]
+fn test_clone_based_game() {
+    use PushfourGame;
+    // NoHash only implements apply(), so it's searched through the default make/unmake
+    let b = test_board();
+    let g = PushfourGame::new();
+    let in_place = Minimax::new().search(3, &g, &b);
+    let cloned = Minimax::new().search(3, &NoHash(&g), &b);
+    assert_eq!(in_place.score, cloned.score);
+}
+
+#[test]
+fn test_either_side_to_move() {
+    use std::str::FromStr;
+    use board::Board;
+    use util::Player;
+    use PushfourGame;
+    // Whoever moves first completes their row of four
+    let mut b = Board::from_str("+ 0 1 2 3 4
+                                 0 - - - - -
+                                 1 # b b b -
+                                 2 - - - - -
+                                 3 - r r r #
+                                 4 - - - - -").unwrap();
+    let g = PushfourGame::new();
+    let mut engine = Minimax::new();
+    let blue = engine.search(3, &g, &b);
+    assert_eq!((Player::Blue, 1, 4), (blue.best_move.player, blue.best_move.row,
+                                      blue.best_move.col));
+    b.next_turn();
+    let red = engine.search(3, &g, &b);
+    assert_eq!((Player::Red, 3, 0), (red.best_move.player, red.best_move.row,
+                                     red.best_move.col));
+    // Same position with colors swapped, same score
+    assert_eq!(blue.score, red.score);
+}
+
+#[test]
+fn test_proven_results() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    // Blue can win now, or (after any reply) later
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 # b b b -
+                             2 - - - - -
+                             3 - - - - -
+                             4 - - - - -").unwrap();
+    let result = Minimax::new().search(5, &g, &b);
+    assert_eq!(Some(Proven::Win(1)), result.proven());
+    assert_eq!((1, 4), (result.best_move.row, result.best_move.col));
+    // Proven at depth 1, so there's no deepening
+    let result = Minimax::best_move_timed(Duration::from_secs(60), &g, &b);
+    assert_eq!((1, Some(Proven::Win(1))), (result.depth, result.proven()));
+
+    // Red can only block one end of Blue's row
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 - - - - -
+                             3 - - - - -
+                             4 - b b b -
+                             Turn: Player Red").unwrap();
+    assert_eq!(None, Minimax::new().search(1, &g, &b).proven());
+    let result = Minimax::new().search(4, &g, &b);
+    assert_eq!(Some(Proven::Loss(2)), result.proven());
+    assert_eq!(2, result.pv.len());
+    assert_eq!("loss in 2", format!("{}", Proven::Loss(2)));
+}
+
+#[test]
+fn test_pv_and_stats() {
+    use PushfourGame;
+    let b = test_board();
+    let g = PushfourGame::new();
+    // Without a table, nothing cuts the variation short
+    let result = Minimax::new().search(4, &NoHash(&g), &b);
+    assert_eq!(4, result.pv.len());
+    assert_eq!(result.best_move, result.pv[0]);
+    // The variation is playable, and ends in the position that got the root its score
+    let mut end = b.clone();
+    for &mv in result.pv.iter() {
+        assert!(end.get_moves_set().contains(&mv));
+        end.make_move(mv);
+    }
+    assert_eq!(result.score, g.eval(&end));
+
+    let s = &result.stats;
+    assert!(s.nodes > s.leaf_evals && s.leaf_evals > 0);
+    assert_eq!(0, s.cutoffs[0]);
+    assert!(s.cutoffs.iter().fold(0, |a, b| a + b) > 0);
+    assert!(s.cutoffs.len() <= 4);
+
+    // Iterative deepening counts the work of every iteration
+    let timed = Minimax::best_move_timed(Duration::from_millis(50), &g, &b);
+    assert!(timed.stats.elapsed >= Duration::from_millis(50) || timed.proven().is_some());
+    assert!(timed.stats.nodes > 0 && timed.stats.nodes_per_second() > 0.0);
+}
+
+#[test]
+fn test_move_ordering() {
+    use board::Board;
+    use util::{Move, Player};
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let b = Board::new(5);
+    let mv = |row, col| Move { row: row, col: col, player: Player::Blue };
+    let mut engine = Minimax::new();
+    // Killers at ply 1 don't count at ply 0
+    engine.record_refutation(&g, mv(4, 0), 1, 3);
+    engine.record_refutation(&g, mv(0, 4), 0, 1);
+    engine.record_refutation(&g, mv(4, 4), 0, 1);
+    engine.record_refutation(&g, mv(4, 4), 0, 2);
+    assert_eq!([Some(mv(4, 4)), Some(mv(0, 4))], engine.killers[0]);
+    let mut moves = vec![mv(0, 0), mv(2, 0), mv(4, 0), mv(0, 4), mv(4, 4), mv(0, 2)];
+    engine.order_moves(&g, &b, &mut moves, Some(mv(0, 0)), 0);
+    // Hash move, killers, the move with history, then closest to the center first
+    assert_eq!(vec![mv(0, 0), mv(4, 4), mv(0, 4), mv(4, 0), mv(2, 0), mv(0, 2)], moves);
+}
+
+#[test]
+fn test_search_options_agree() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    let positions = vec![test_board(),
+                         Board::from_str("+ 0 1 2 3 4 5
+                                          0 - - - - - -
+                                          1 - # - b - -
+                                          2 - - r - - -
+                                          3 - b r - # -
+                                          4 - - - - - -
+                                          Turn: Player Red").unwrap()];
+    let g = PushfourGame::new();
+    for b in positions {
+        let plain = Minimax::new().search(5, &g, &b);
+        let mut engine = Minimax::new();
+        engine.set_options(SearchOptions { pvs: true, ..SearchOptions::default() });
+        let pvs = engine.search(5, &g, &b);
+        assert_eq!(plain.score, pvs.score);
+        assert!(pvs.stats.pvs_researches > 0);
+
+        // Iterative deepening to the same depth, with a window small enough to miss
+        for &opts in [SearchOptions { aspiration: Some(1), ..SearchOptions::default() },
+                      SearchOptions { pvs: true, aspiration: Some(1),
+                                      ..SearchOptions::default() }].iter() {
+            let mut engine = Minimax::new();
+            engine.set_options(opts);
+            let mut result = engine.search(1, &g, &b);
+            for depth in 2..6 {
+                result = engine.search_iteration(depth, &g, &b, result.score).unwrap();
+            }
+            assert_eq!(plain.score, result.score);
+            assert!(result.stats.aspiration_researches > 0);
+        }
+    }
+}
+
+#[test]
+fn test_table_mate_scores() {
+    // A win 3 plies below a node at ply 2 is 5 plies from the root, and 3 from the node
+    let score = WIN_SCORE - 5;
+    assert_eq!(WIN_SCORE - 3, score_to_table(score, 2));
+    assert_eq!(WIN_SCORE - 7, score_from_table(WIN_SCORE - 3, 4));
+    assert_eq!(-(WIN_SCORE - 3), score_to_table(-score, 2));
+    assert_eq!(42, score_to_table(42, 2));
+}
+
+// Hides a game's hash key and in-place moves, so it gets searched without a transposition table,
+// by cloning states
+#[cfg(test)]
+struct NoHash<'a, G: 'a>(&'a G);
+
+#[cfg(test)]
+impl<'a, State: Clone + Debug, Move: Copy, G: Game<State, Move>> Game<State, Move> for NoHash<'a, G> {
+    fn get_moves(&self, s: &State) -> Vec<Move> { self.0.get_moves(s) }
+    fn eval(&self, s: &State) -> i32 { self.0.eval(s) }
+    fn apply(&self, s: &State, m: Move) -> State { self.0.apply(s, m) }
+    fn gameover(&self, s: &State) -> bool { self.0.gameover(s) }
+    fn outcome(&self, s: &State) -> Option<Outcome> { self.0.outcome(s) }
+}
+
+#[test]
+fn test_parallel_agrees_with_search() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    let positions = vec![test_board(),
+                         Board::from_str("+ 0 1 2 3 4 5
+                                          0 - - - - - -
+                                          1 - # - b - -
+                                          2 - - r - - -
+                                          3 - b r - # -
+                                          4 - - - - - -
+                                          Turn: Player Red").unwrap()];
+    let g = PushfourGame::new();
+    for b in positions {
+        for depth in 1..6 {
+            let plain = Minimax::new().search(depth, &g, &b);
+            for &threads in [2, 3, 8].iter() {
+                let mut engine = Minimax::new();
+                engine.set_options(SearchOptions { threads: threads, ..SearchOptions::default() });
+                let parallel = engine.search_parallel(depth, &g, &b);
+                assert_eq!(plain.score, parallel.score);
+                assert_eq!(plain.best_move, parallel.best_move);
+                assert_eq!(Some(&parallel.best_move), parallel.pv.first());
+                assert_eq!(threads, engine.workers.len());
+                // The workers' tables count towards the engine's
+                let stats = engine.table_stats();
+                if depth > 1 { assert!(stats.misses > 1 && stats.stores > 1); }
+                // Again, with the workers' tables already filled in
+                assert_eq!(plain.score, engine.search_parallel(depth, &g, &b).score);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stop_handle() {
+    use std::sync::mpsc;
+    use board::Board;
+    use util::Move;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let b = Board::new(8);
+
+    // Already stopped: a fixed-depth search falls back to depth 1
+    let mut engine = Minimax::new();
+    engine.stop_handle().stop();
+    let result = engine.search(8, &g, &b);
+    assert!(result.stopped);
+    assert_eq!(1, result.depth);
+    assert!(b.get_moves_set().contains(&result.best_move));
+    let result = engine.search_timed(Duration::from_secs(60), &g, &b);
+    assert!(result.stopped);
+    assert_eq!(1, result.depth);
+    engine.stop_handle().reset();
+    assert!(!engine.search(1, &g, &b).stopped);
+
+    // Stopped from another thread, mid-search, with a progress update for each finished depth
+    struct Progress(mpsc::Sender<(i32, i32, usize)>);
+    impl SearchObserver<Move> for Progress {
+        fn iteration(&mut self, r: &SearchResult<Move>) {
+            self.0.send((r.depth, r.score, r.pv.len())).unwrap();
+        }
+    }
+    let (tx, rx) = mpsc::channel();
+    let mut engine = Minimax::new();
+    engine.set_observer(Some(Box::new(Progress(tx))));
+    let stop = engine.stop_handle();
+    let root = b.clone();
+    let searcher = thread::spawn(move || {
+        engine.search_timed(Duration::from_secs(600), &g, &root)
+    });
+    let (first, _, pv_len) = rx.recv().unwrap();
+    assert_eq!(1, first);
+    assert_eq!(1, pv_len);
+    stop.stop();
+    let result = searcher.join().unwrap();
+    assert!(result.stopped);
+    assert!(b.get_moves_set().contains(&result.best_move));
+    let depths: Vec<i32> = rx.iter().map(|(d, _, _)| d).collect();
+    assert_eq!((2..result.depth + 1).collect::<Vec<i32>>(), depths);
+}
+
+#[test]
+fn test_multipv() {
+    use PushfourGame;
+    let b = test_board();
+    let g = PushfourGame::new();
+    for depth in 1..5 {
+        let plain = Minimax::new().search(depth, &g, &b);
+        let lines = Minimax::new().search_multipv(depth, 4, &g, &b);
+        assert_eq!(4, lines.len());
+        assert_eq!(plain.score, lines[0].score);
+        assert_eq!(plain.best_move, lines[0].best_move);
+        for (i, l) in lines.iter().enumerate() {
+            assert_eq!(Some(&l.best_move), l.pv.first());
+            if i > 0 { assert!(lines[i - 1].score >= l.score); }
+            // Each score is exact: the same as searching the move on its own
+            let child = g.apply(&b, l.best_move);
+            let score = if depth > 1 { -Minimax::new().search(depth - 1, &g, &child).score }
+                        else { -g.eval(&child) };
+            assert_eq!(score, l.score);
+        }
+    }
+    // Asking for more moves than there are gives all of them
+    let all = Minimax::new().search_multipv(2, 1000, &g, &b);
+    assert_eq!(b.legal_move_count() as usize, all.len());
+}
+
+#[test]
+fn test_forcing_extension() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    // Blue threatens 1:0 and 4:3, and Red can only block one of them
+    let b = Board::from_str("+ 0 1 2 3 4
+                             0 - - - - -
+                             1 - - - - -
+                             2 b - - - -
+                             3 b - - - r
+                             4 b b b - r
+                             Turn: Player Red").unwrap();
+    let g = PushfourGame::new();
+    let plain = Minimax::new().search(1, &g, &b);
+    assert_eq!(None, plain.proven());
+    let mut engine = Minimax::new();
+    engine.set_options(SearchOptions { extension: 2, ..SearchOptions::default() });
+    let extended = engine.search(1, &g, &b);
+    assert_eq!(Some(Proven::Loss(2)), extended.proven());
+    assert_eq!(extended.score, Minimax::new().search(2, &g, &b).score);
+
+    // The extension only follows forcing moves, so a quiet position is searched as before
+    let quiet = Board::new(5);
+    let plain = Minimax::new().search(2, &g, &quiet);
+    let extended = engine.search(2, &g, &quiet);
+    assert_eq!(plain.score, extended.score);
+    assert_eq!(plain.stats.nodes, extended.stats.nodes);
+}
+
+#[test]
+fn test_endgame_solver() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let mut engine = Minimax::new();
+    engine.set_options(SearchOptions { endgame: 8, ..SearchOptions::default() });
+
+    // Blue gets three in a row, Red blocks the fourth, and the board is full
+    let b = Board::from_str("+ 0 1 2 3
+                             0 # # # #
+                             1 b b - -
+                             2 # # # #
+                             3 # # # #").unwrap();
+    assert_eq!(Some(2), g.moves_left(&b));
+    assert_eq!(None, Minimax::new().search(1, &g, &b).proven());
+    let result = engine.search(1, &g, &b);
+    assert!(result.solved);
+    assert_eq!((2, Some(Proven::Draw)), (result.depth, result.proven()));
+    assert_eq!("draw", format!("{}", Proven::Draw));
+
+    // Solved without evaluating anything, with the same result as a search to the end
+    let b = Board::from_str("+ 0 1 2 3
+                             0 # b - -
+                             1 r b - #
+                             2 r - - #
+                             3 # b r -
+                             Turn: Player Red").unwrap();
+    let result = engine.search(1, &g, &b);
+    assert!(result.solved && result.proven().is_some());
+    assert_eq!(0, result.stats.leaf_evals);
+    assert_eq!(Minimax::new().search(6, &g, &b).score, result.score);
+    let timed = engine.search_timed(Duration::from_secs(60), &g, &b);
+    assert_eq!((result.score, true), (timed.score, timed.solved));
+
+    // Too many cells left to solve
+    assert!(!engine.search(1, &g, &Board::new(4)).solved);
+}
+
+#[test]
+fn test_endgame_solver_keeps_to_budget() {
+    use board::Board;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let mut engine = Minimax::new();
+    engine.set_options(SearchOptions { endgame: 100, ..SearchOptions::default() });
+    let b = Board::new_rect(5, 4);
+    let result = engine.search_timed(Duration::from_millis(10), &g, &b);
+    assert!(result.stats.elapsed < Duration::from_secs(2));
+    assert!(!result.solved);
+    assert_eq!(1, result.depth);
+    assert!(b.get_moves_set().contains(&result.best_move));
+}
@@ -15,32 +15,58 @@ fn score_row_limit(mut row: u64, limit: usize) -> i32 {
     i
 }
 
-// Returns the length of the longest contiguous segment of 4 or less
+// Returns the length of the longest contiguous segment of `connect` or less
 #[inline(always)]
-fn score_row(row: u64) -> i32 {
-    score_row_limit(row, 4)
+fn score_row(row: u64, connect: usize) -> i32 {
+    score_row_limit(row, connect)
 }
 
-// Returns the length of the longest segment that would be >= 4 long after ORing with `mask`.
-// Returns 0 if there is no available win in `row | mask`.
+// Returns the length of the longest segment that would be >= `connect` long after ORing with
+// `mask`. Returns 0 if there is no available win in `row | mask`.
 #[inline(always)]
-fn score_row_mask(row: u64, mask: u64, size: usize) -> i32 {
+fn score_row_mask(row: u64, mask: u64, size: usize, connect: usize) -> i32 {
     if row == 0 { return 0; }
     let combined = row | mask;
     let combined_score = score_row_limit(combined, size);
-    if combined_score < 4 { return 0; }
+    if combined_score < connect as i32 { return 0; }
     let mask_score = score_row_limit(mask, size);
 
     // For this to be true, the row 1's must touch the mask 1's.
     if combined_score > mask_score {
-        return score_row(row);
+        return score_row(row, connect);
     }
     0
 }
 
 #[inline(always)]
-fn is_row_win(row: u64) -> bool {
-    score_row(row) >= 4
+fn is_row_win(row: u64, connect: usize) -> bool {
+    score_row(row, connect) >= connect as i32
+}
+
+// A winning segment scores triple, so that a win outweighs any lead in unfinished segments
+#[inline(always)]
+fn boost_win(score: i32, connect: usize) -> i32 {
+    if score >= connect as i32 { score * 3 } else { score }
+}
+
+// Counts the `connect`-long windows in a line `len` cells long that contain no `blocked` cells,
+// by how many `row` cells they contain, and returns the positions of the empty cells of windows
+// that are one short of full
+fn line_windows(row: u64, blocked: u64, len: usize, connect: usize,
+                counts: &mut [u32]) -> Vec<usize> {
+    let mut threats = Vec::new();
+    if len < connect { return threats; }
+    let full = (1u64 << connect) - 1;
+    for start in 0..len - connect + 1 {
+        let window = full << start;
+        if blocked & window != 0 { continue; }
+        let n = (row & window).count_ones() as usize;
+        counts[n] += 1;
+        if n + 1 == connect {
+            threats.push((window & !row).trailing_zeros() as usize);
+        }
+    }
+    threats
 }
 
 #[inline(always)]
@@ -60,15 +86,18 @@ fn reachable_in_row(row: u64) -> u64 {
     reach
 }
 
-// Stores the positions of one type of piece on a square board. Maintains four different representations
-// of the board, one for each 45-degree rotation.
+// Stores the positions of one type of piece on a board of up to MAX_BOARD_DIM rows and columns.
+// Maintains four different representations of the board, one for each 45-degree rotation:
+// `main` has a u64 per row, `invert` a u64 per column, and `diag` and `diag_rot` a u64 per
+// diagonal (see diag_lookup).
 #[derive(Clone)]
 pub struct Overlay {
-    size: usize,
-    pub main: [u64; BOARD_SIZE],
-    pub invert: [u64; BOARD_SIZE],
-    diag: [u64; BOARD_DIAG_SIZE],
-    diag_rot: [u64; BOARD_DIAG_SIZE],
+    width: usize,
+    height: usize,
+    pub main: Vec<u64>,
+    pub invert: Vec<u64>,
+    diag: Vec<u64>,
+    diag_rot: Vec<u64>,
 }
 
 impl fmt::Debug for Overlay {
@@ -76,14 +105,14 @@ impl fmt::Debug for Overlay {
         let mut grid = String::new();
         grid.push_str("\n+ ");
         let mut col = 0;
-        while col < self.size { grid.push_str(&*format!("{} ", col)); col += 1; }
+        while col < self.width { grid.push_str(&*format!("{} ", col)); col += 1; }
         grid.push_str("\n");
         let mut row = 0;
-        while row < self.size {
+        while row < self.height {
             let mut col = 0;
             let row_bits = self.main[row];
             grid.push_str(&*format!("{} ", row));
-            while col < self.size {
+            while col < self.width {
                 let mut val = '-';
                 let mask = 1 << col;
                 if row_bits & mask > 0 {
@@ -100,21 +129,32 @@ impl fmt::Debug for Overlay {
 }
 
 impl Overlay {
-    pub fn new(size: usize) -> Overlay {
+    pub fn new(width: usize, height: usize) -> Overlay {
+        assert!(width > 0 && width <= MAX_BOARD_DIM && height > 0 && height <= MAX_BOARD_DIM,
+                "unsupported board dimensions {}x{}", width, height);
         Overlay {
-            size: size,
-            main: [0; BOARD_SIZE],
-            invert: [0; BOARD_SIZE],
-            diag: [0; BOARD_DIAG_SIZE],
-            diag_rot: [0; BOARD_DIAG_SIZE],
+            width: width,
+            height: height,
+            main: vec![0; height],
+            invert: vec![0; width],
+            diag: vec![0; width + height - 1],
+            diag_rot: vec![0; width + height - 1],
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn set(&mut self, row: usize, col: usize) {
         let (row_invert, col_invert) = (col, row);
         self.main[row] |= 1 << col;
         self.invert[row_invert] |= 1 << col_invert;
-        let (diag_coord, diag_rot_coord) = diag_lookup::lookup(self.size, row, col);
+        let (diag_coord, diag_rot_coord) = diag_lookup::lookup(self.width, self.height, row, col);
         self.diag[diag_coord.0] |= 1 << diag_coord.1;
         self.diag_rot[diag_rot_coord.0] |= 1 << diag_rot_coord.1;
     }
@@ -123,7 +163,7 @@ impl Overlay {
         let (row_invert, col_invert) = (col, row);
         self.main[row] &= !0 ^ (1 << col);
         self.invert[row_invert] &= !0 ^ (1 << col_invert);
-        let (diag_coord, diag_rot_coord) = diag_lookup::lookup(self.size, row, col);
+        let (diag_coord, diag_rot_coord) = diag_lookup::lookup(self.width, self.height, row, col);
         self.diag[diag_coord.0] &= !0 ^ (1 << diag_coord.1);
         self.diag_rot[diag_rot_coord.0] &= !0 ^ (1 << diag_rot_coord.1);
     }
@@ -133,16 +173,16 @@ impl Overlay {
     }
 
     pub fn reachable(&self) -> Overlay {
-        let mut o = Overlay::new(self.size);
-        for row in 0..self.size {
+        let mut o = Overlay::new(self.width, self.height);
+        for row in 0..self.height {
             let r = reachable_in_row(self.main[row]);
-            for col in 0..self.size {
+            for col in 0..self.width {
                 if (r & (1 << col)) > 0 { o.set(row, col); }
             }
         }
-        for col in 0..self.size {
+        for col in 0..self.width {
             let r = reachable_in_row(self.invert[col]);
-            for row in 0..self.size {
+            for row in 0..self.height {
                 if r & (1 << row) > 0 { o.set(row, col); }
             }
         }
@@ -156,47 +196,87 @@ impl Overlay {
         for (i, row) in self.diag_rot.iter_mut().enumerate() { *row |= other.diag_rot[i]; }
     }
 
-    pub fn score(&self) -> i32 {
+    // Length of the longest segment, up to `connect` (which scores triple)
+    pub fn score(&self, connect: usize) -> i32 {
         let mut score = 0;
-        for row in &self.main { score = core::cmp::max(score, score_row(*row)); }
-        for row in &self.invert { score = core::cmp::max(score, score_row(*row)); }
-        for row in &self.diag { score = core::cmp::max(score, score_row(*row)); }
-        for row in &self.diag_rot { score = core::cmp::max(score, score_row(*row)); }
-        score | ((score & 4) << 1) // boost score if score == 4
+        for row in &self.main { score = core::cmp::max(score, score_row(*row, connect)); }
+        for row in &self.invert { score = core::cmp::max(score, score_row(*row, connect)); }
+        for row in &self.diag { score = core::cmp::max(score, score_row(*row, connect)); }
+        for row in &self.diag_rot { score = core::cmp::max(score, score_row(*row, connect)); }
+        boost_win(score, connect)
     }
 
-    pub fn score_with_mask(&self, mask: &Overlay) -> i32 {
+    // Like score(), but only counts lines that could still be completed using the cells in `mask`
+    pub fn score_with_mask(&self, mask: &Overlay, connect: usize) -> i32 {
+        let size = core::cmp::max(self.width, self.height);
         let mut score = 0;
         for (i, row) in self.main.iter().enumerate() {
-            score = core::cmp::max(score, score_row_mask(*row, mask.main[i], self.size));
+            score = core::cmp::max(score, score_row_mask(*row, mask.main[i], size, connect));
         }
         for (i, row) in self.invert.iter().enumerate() {
-            score = core::cmp::max(score, score_row_mask(*row, mask.invert[i], self.size));
+            score = core::cmp::max(score, score_row_mask(*row, mask.invert[i], size, connect));
         }
         for (i, row) in self.diag.iter().enumerate() {
-            score = core::cmp::max(score, score_row_mask(*row, mask.diag[i], self.size));
+            score = core::cmp::max(score, score_row_mask(*row, mask.diag[i], size, connect));
         }
         for (i, row) in self.diag_rot.iter().enumerate() {
-            score = core::cmp::max(score, score_row_mask(*row, mask.diag_rot[i], self.size));
+            score = core::cmp::max(score, score_row_mask(*row, mask.diag_rot[i], size, connect));
         }
-        score | ((score & 4) << 1) // boost score if score == 4
+        boost_win(score, connect)
     }
 
-    // Faster than just checking if the total board score is > ROW_WIN_SCORE
-    pub fn is_win_state(&self) -> bool {
-        for row in &self.main { if is_row_win(*row) { return true; } }
-        for row in &self.invert { if is_row_win(*row) { return true; } }
-        for row in &self.diag { if is_row_win(*row) { return true; } }
-        for row in &self.diag_rot { if is_row_win(*row) { return true; } }
-        false
+    // Number of cells set
+    pub fn count(&self) -> u32 {
+        self.main.iter().fold(0, |n, row| n + row.count_ones())
     }
 
+    // Counts the windows of `connect` cells in a line (in any direction) that don't contain any
+    // `blocked` cells, by how many of this overlay's cells they contain: the returned counts are
+    // indexed 0 to `connect`. Also returns the cells that would complete a window, i.e. the empty
+    // cell of each window that's one short.
+    pub fn windows(&self, blocked: &Overlay, connect: usize) -> (Vec<u32>, Overlay) {
+        let (w, h) = (self.width, self.height);
+        let mut counts = vec![0; connect + 1];
+        let mut threats = Overlay::new(w, h);
+        for row in 0..h {
+            for col in line_windows(self.main[row], blocked.main[row], w, connect, &mut counts) {
+                threats.set(row, col);
+            }
+        }
+        for col in 0..w {
+            for row in line_windows(self.invert[col], blocked.invert[col], h, connect,
+                                    &mut counts) {
+                threats.set(row, col);
+            }
+        }
+        for d in 0..w + h - 1 {
+            let len = diag_lookup::diag_len(w, h, d);
+            for i in line_windows(self.diag[d], blocked.diag[d], len, connect, &mut counts) {
+                let (row, col) = diag_lookup::main_cell(w, h, d, i);
+                threats.set(row, col);
+            }
+            for i in line_windows(self.diag_rot[d], blocked.diag_rot[d], len, connect,
+                                  &mut counts) {
+                let (row, col) = diag_lookup::rot_cell(w, h, d, i);
+                threats.set(row, col);
+            }
+        }
+        (counts, threats)
+    }
 
+    // Whether there are `connect` in a row anywhere. Faster than checking the score.
+    pub fn is_win_state(&self, connect: usize) -> bool {
+        for row in &self.main { if is_row_win(*row, connect) { return true; } }
+        for row in &self.invert { if is_row_win(*row, connect) { return true; } }
+        for row in &self.diag { if is_row_win(*row, connect) { return true; } }
+        for row in &self.diag_rot { if is_row_win(*row, connect) { return true; } }
+        false
+    }
 }
 
 #[test]
 fn test_get_set() {
-    let mut b = Overlay::new(4);
+    let mut b = Overlay::new(4, 4);
     assert_eq!(false, b.get(1, 0));
     b.set(0, 0);
     assert_eq!(true, b.get(0, 0));
@@ -205,54 +285,73 @@ fn test_get_set() {
 
 #[test]
 fn test_is_row_win() {
-    assert!(!is_row_win(0));
-    assert!(!is_row_win(1));
-    assert!(!is_row_win(0b11));
-    assert!(!is_row_win(0b111));
-    assert!(is_row_win(0b1111));
-    assert!(is_row_win(0b11110));
-    assert!(is_row_win(0xF000000000000000));
-    assert!(is_row_win(0xAA02F20011002345));
-    assert!(!is_row_win(0xAA55000011002345));
-    assert!(is_row_win(0x1E00000000000000));
-    assert!(!is_row_win(0xE000000000000000));
+    assert!(!is_row_win(0, 4));
+    assert!(!is_row_win(1, 4));
+    assert!(!is_row_win(0b11, 4));
+    assert!(!is_row_win(0b111, 4));
+    assert!(is_row_win(0b1111, 4));
+    assert!(is_row_win(0b11110, 4));
+    assert!(is_row_win(0xF000000000000000, 4));
+    assert!(is_row_win(0xAA02F20011002345, 4));
+    assert!(!is_row_win(0xAA55000011002345, 4));
+    assert!(is_row_win(0x1E00000000000000, 4));
+    assert!(!is_row_win(0xE000000000000000, 4));
 }
 
 #[test]
 fn test_score_row() {
-    assert_eq!(0, score_row(0));
-    assert_eq!(1, score_row(1));
-    assert_eq!(2, score_row(0b11));
-    assert_eq!(3, score_row(0b111));
-    assert_eq!(4, score_row(0b1111));
-    assert_eq!(4, score_row(0b11110));
-    assert_eq!(4, score_row(0xF000000000000000));
-    assert_eq!(4, score_row(0xAA02F20011002345));
-    assert_eq!(2, score_row(0xAA55000011002345));
-    assert_eq!(4, score_row(0x1E00000000000000));
-    assert_eq!(3, score_row(0xE000000000000000));
+    assert_eq!(0, score_row(0, 4));
+    assert_eq!(1, score_row(1, 4));
+    assert_eq!(2, score_row(0b11, 4));
+    assert_eq!(3, score_row(0b111, 4));
+    assert_eq!(4, score_row(0b1111, 4));
+    assert_eq!(4, score_row(0b11110, 4));
+    assert_eq!(4, score_row(0xF000000000000000, 4));
+    assert_eq!(4, score_row(0xAA02F20011002345, 4));
+    assert_eq!(2, score_row(0xAA55000011002345, 4));
+    assert_eq!(4, score_row(0x1E00000000000000, 4));
+    assert_eq!(3, score_row(0xE000000000000000, 4));
 }
 
 #[test]
 fn test_score_row_mask() {
-    assert_eq!(0, score_row_mask(0, 0, 4));
-    assert_eq!(2, score_row_mask(0b0110, 0b1001, 4));
-    assert_eq!(0, score_row_mask(0b0110, 0b0000, 4));
-    assert_eq!(2, score_row_mask(0b11, 0b1100, 4));
-    assert_eq!(3, score_row_mask(0b111, 0b1000, 4));
-    assert_eq!(0, score_row_mask(0b11, 0b11000, 4));
-    assert_eq!(4, score_row_mask(0b1111, 0b0, 4));
-    assert_eq!(0, score_row_mask(0b11, 0b1111000, 4));
-    assert_eq!(1, score_row_mask(0b1000, 0b0111, 4));
+    assert_eq!(0, score_row_mask(0, 0, 4, 4));
+    assert_eq!(2, score_row_mask(0b0110, 0b1001, 4, 4));
+    assert_eq!(0, score_row_mask(0b0110, 0b0000, 4, 4));
+    assert_eq!(2, score_row_mask(0b11, 0b1100, 4, 4));
+    assert_eq!(3, score_row_mask(0b111, 0b1000, 4, 4));
+    assert_eq!(0, score_row_mask(0b11, 0b11000, 4, 4));
+    assert_eq!(4, score_row_mask(0b1111, 0b0, 4, 4));
+    assert_eq!(0, score_row_mask(0b11, 0b1111000, 4, 4));
+    assert_eq!(1, score_row_mask(0b1000, 0b0111, 4, 4));
+}
+
+#[test]
+fn test_connect_length() {
+    assert!(is_row_win(0b111, 3));
+    assert!(!is_row_win(0b1111, 5));
+    assert!(is_row_win(0b111110, 5));
+    assert_eq!(3, score_row(0b1111, 3));
+    assert_eq!(0, score_row_mask(0b11, 0b1100, 4, 5));
+    assert_eq!(2, score_row_mask(0b11, 0b11100, 5, 5));
+
+    let mut o = Overlay::new(5, 5);
+    o.set(0, 0);
+    o.set(1, 1);
+    o.set(2, 2);
+    assert!(o.is_win_state(3));
+    assert!(!o.is_win_state(4));
+    assert_eq!(9, o.score(3));
+    assert_eq!(3, o.score(4));
 }
 
 #[test]
 fn test_overlay_merge() {
-    let mut o = Overlay::new(3);
+    let mut o = Overlay::new(3, 3);
     o.set(1, 2);
     assert!(o.get(1, 2));
 
-    let mut o1 = Overlay::new(3);
+    let mut o1 = Overlay::new(3, 3);
     o1.set(1, 0);
     o.merge(&o1);
     assert!(o.get(1, 0));
@@ -270,7 +369,7 @@ fn test_reachable_in_row() {
 
 #[test]
 fn test_overlay_reachable_0() {
-    let mut o = Overlay::new(3);
+    let mut o = Overlay::new(3, 3);
     o.set(0, 0);
     o.set(0, 2);
     println!("{:?}", o);
@@ -283,7 +382,7 @@ fn test_overlay_reachable_0() {
 
 #[test]
 fn test_overlay_reachable_1() {
-    let mut o = Overlay::new(3);
+    let mut o = Overlay::new(3, 3);
     o.set(0, 1);
     o.set(1, 2);
     o.set(2, 1);
@@ -296,3 +395,50 @@ fn test_overlay_reachable_1() {
     assert!(r.get(0, 2));
     assert!(r.get(2, 0));
 }
+
+#[test]
+fn test_overlay_rect_wins() {
+    // 3 rows, 9 columns: a diagonal win near the right edge
+    let mut o = Overlay::new(9, 3);
+    o.set(0, 6);
+    o.set(1, 7);
+    o.set(2, 8);
+    assert!(!o.is_win_state(4));
+    let mut o = Overlay::new(4, 9);
+    o.set(8, 0);
+    o.set(7, 1);
+    o.set(6, 2);
+    o.set(5, 3);
+    assert!(o.is_win_state(4));
+    let mut o = Overlay::new(2, 9);
+    for row in 4..8 { o.set(row, 1); }
+    assert!(o.is_win_state(4));
+}
+
+#[test]
+fn test_overlay_max_size() {
+    let mut o = Overlay::new(MAX_BOARD_DIM, MAX_BOARD_DIM);
+    for i in 0..4 { o.set(MAX_BOARD_DIM - 1 - i, MAX_BOARD_DIM - 1 - i); }
+    assert!(o.is_win_state(4));
+    let r = o.reachable();
+    assert!(r.get(MAX_BOARD_DIM - 1, 0));
+    assert!(!r.get(MAX_BOARD_DIM - 1, MAX_BOARD_DIM - 1));
+}
+
+#[test]
+fn test_windows() {
+    // r - b b - -
+    // - - - - - -
+    // - - - - b -
+    let mut mine = Overlay::new(6, 3);
+    mine.set(0, 2);
+    mine.set(0, 3);
+    mine.set(2, 4);
+    let mut blocked = Overlay::new(6, 3);
+    blocked.set(0, 0);
+    let (counts, threats) = mine.windows(&blocked, 3);
+    // 23 open windows: 11 in rows, 5 in columns, 4 anti-diagonal and 3 diagonal
+    assert_eq!(vec![11, 9, 3, 0], counts);
+    assert!(threats.get(0, 1) && threats.get(0, 4) && threats.get(1, 3));
+    assert_eq!(3, threats.count());
+}
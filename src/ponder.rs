@@ -0,0 +1,157 @@
+// Pondering: searching on the opponent's time. A Ponderer owns a Minimax engine and, between
+// moves, lends it to a background thread that searches the position the engine expects to face
+// next. The searches it has to do for real afterwards find that work in the transposition table.
+
+use std::fmt::Debug;
+use std::mem;
+use std::thread::{self, JoinHandle};
+use minimax::{Game, Minimax, SearchOptions, StopHandle};
+
+pub struct Ponderer<Move> {
+    // None while the engine is off pondering
+    engine: Option<Minimax<Move>>,
+    pondering: Option<Pondering<Move>>,
+}
+
+struct Pondering<Move> {
+    stop: StopHandle,
+    handle: JoinHandle<Minimax<Move>>,
+    // What the engine is set up with, in case the search panics and loses it
+    own_stop: StopHandle,
+    options: SearchOptions,
+}
+
+impl<Move: Copy + PartialEq + Send + 'static> Ponderer<Move> {
+    pub fn new(engine: Minimax<Move>) -> Ponderer<Move> {
+        Ponderer {
+            engine: Some(engine),
+            pondering: None,
+        }
+    }
+
+    // Starts searching `state` in the background until the engine is needed again. Pass the
+    // position after the opponent's most likely reply to ponder that reply, or the position
+    // they're to move in to ponder all of them. There's nothing to ponder in a finished game, so
+    // that just stops any pondering already going on.
+    pub fn start<State, GameType>(&mut self, game: &GameType, state: &State)
+        where State: Clone + Debug + Send + 'static,
+              GameType: Game<State, Move> + Clone + Send + 'static {
+        let mut engine = self.take_engine();
+        if game.outcome(state).is_some() || game.gameover(state) ||
+           game.get_moves(state).is_empty() {
+            self.engine = Some(engine);
+            return;
+        }
+        // The engine's own stop handle is left alone, so it isn't stopped along with pondering
+        let stop = StopHandle::new();
+        let own_stop = engine.stop_handle();
+        engine.set_stop_handle(stop.clone());
+        let (game, state) = (game.clone(), state.clone());
+        let options = engine.options();
+        let restore = own_stop.clone();
+        let handle = thread::spawn(move || {
+            engine.search_until_stopped(&game, &state);
+            engine.set_stop_handle(restore);
+            engine
+        });
+        self.pondering = Some(Pondering {
+            stop: stop,
+            handle: handle,
+            own_stop: own_stop,
+            options: options,
+        });
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.is_some()
+    }
+
+    // Stops pondering, if it's going on, and returns the engine for a search of its own
+    pub fn engine(&mut self) -> &mut Minimax<Move> {
+        if self.engine.is_none() {
+            let engine = self.take_engine();
+            self.engine = Some(engine);
+        }
+        self.engine.as_mut().unwrap()
+    }
+
+    // Ends pondering and hands back the engine
+    pub fn into_engine(mut self) -> Minimax<Move> {
+        self.take_engine()
+    }
+
+    fn take_engine(&mut self) -> Minimax<Move> {
+        if let Some(p) = mem::replace(&mut self.pondering, None) {
+            p.stop.stop();
+            // A search that panicked took the engine down with it. Carry on with a fresh one,
+            // set up the same bar the table and observer, rather than pass the panic on.
+            let (own_stop, options) = (p.own_stop, p.options);
+            return p.handle.join().unwrap_or_else(|_| {
+                let mut engine = Minimax::new();
+                engine.set_options(options);
+                engine.set_stop_handle(own_stop);
+                engine
+            });
+        }
+        self.engine.take().expect("engine is neither idle nor pondering")
+    }
+}
+
+#[test]
+fn test_ponder() {
+    use board::Board;
+    use util::{Move, Player};
+    use PushfourGame;
+    let g = PushfourGame::new();
+    let b = Board::new(6);
+    let reply = Move { row: 0, col: 0, player: Player::Blue };
+    let expected = g.apply(&b, reply);
+
+    let mut p = Ponderer::new(Minimax::new());
+    p.start(&g, &expected);
+    assert!(p.is_pondering());
+    // However soon it's stopped, pondering leaves something in the table
+    let result = {
+        let engine = p.engine();
+        assert!(engine.table_stats().stores > 0);
+        engine.search(3, &g, &expected)
+    };
+    assert!(!p.is_pondering());
+    assert!(!result.stopped && !result.pv.is_empty());
+    assert!(expected.get_moves_set().contains(&result.best_move));
+
+    // Stopping the ponder doesn't stop the engine's own searches
+    p.start(&g, &b);
+    let mut engine = p.into_engine();
+    assert!(!engine.stop_handle().is_stopped());
+    assert!(!engine.search(2, &g, &b).stopped);
+}
+
+#[test]
+fn test_ponder_finished_game() {
+    use std::str::FromStr;
+    use board::Board;
+    use PushfourGame;
+    let g = PushfourGame::new();
+    // Won, and full
+    let won = Board::from_str("+ 0 1 2 3
+                               0 b b b b
+                               1 r r r -
+                               2 - - - -
+                               3 - - - -").unwrap();
+    let full = Board::from_str("+ 0 1
+                                0 b r
+                                1 r b").unwrap();
+    let mut p = Ponderer::new(Minimax::new());
+    for b in [&won, &full].iter() {
+        assert!(g.outcome(b).is_some() || g.get_moves(b).is_empty());
+        p.start(&g, b);
+        assert!(!p.is_pondering());
+        assert!(!p.engine().stop_handle().is_stopped());
+    }
+    // Pondering already going on stops
+    p.start(&g, &Board::new(5));
+    p.start(&g, &won);
+    assert!(!p.is_pondering());
+    p.into_engine();
+}
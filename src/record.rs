@@ -0,0 +1,496 @@
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use board::{Board, NotationError};
+use util::*;
+
+// How a recorded game ended. In text, written as in PGN with Blue (who moves first) as white.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    BlueWins,
+    RedWins,
+    Draw,
+    Unfinished,
+}
+
+impl GameResult {
+    // The result a position stands for, if the game is over there
+    pub fn of(b: &Board) -> GameResult {
+        if b.is_win_state(Player::Blue) { return GameResult::BlueWins; }
+        if b.is_win_state(Player::Red) { return GameResult::RedWins; }
+        if b.legal_move_count() == 0 { return GameResult::Draw; }
+        GameResult::Unfinished
+    }
+
+    fn token(&self) -> &'static str {
+        match *self {
+            GameResult::BlueWins => "1-0",
+            GameResult::RedWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+
+    fn from_token(s: &str) -> Option<GameResult> {
+        match s {
+            "1-0" => Some(GameResult::BlueWins),
+            "0-1" => Some(GameResult::RedWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unfinished),
+            _ => None,
+        }
+    }
+}
+
+// Why a recorded game doesn't replay. Plies count from 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    WrongPlayer { ply: usize, mv: Move },
+    IllegalMove { ply: usize, mv: Move },
+    AfterGameOver { ply: usize, mv: Move },
+    // The recorded result isn't what the final position says
+    ResultMismatch { recorded: GameResult, actual: GameResult },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::WrongPlayer { ply, mv } =>
+                write!(f, "ply {}: {} is out of turn", ply, mv),
+            ReplayError::IllegalMove { ply, mv } =>
+                write!(f, "ply {}: {} is illegal", ply, mv),
+            ReplayError::AfterGameOver { ply, mv } =>
+                write!(f, "ply {}: {} comes after the game ended", ply, mv),
+            ReplayError::ResultMismatch { recorded, actual } =>
+                write!(f, "recorded result {} but the game ended {}", recorded.token(),
+                       actual.token()),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn description(&self) -> &str {
+        "invalid game record"
+    }
+}
+
+// Why a record's text couldn't be parsed. Lines count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordParseError {
+    BadTag { line: usize },
+    // Neither a Size nor a Position tag says what board the game was played on
+    MissingBoard,
+    BadSize(String),
+    BadRocks(String),
+    BadPosition(NotationError),
+    BadMove { line: usize, token: String },
+    BadResult(String),
+    // The Result tag and the end of the move text disagree
+    ConflictingResults,
+}
+
+impl fmt::Display for RecordParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordParseError::BadTag { line } => write!(f, "line {}: malformed tag", line),
+            RecordParseError::MissingBoard => write!(f, "no Size or Position tag"),
+            RecordParseError::BadSize(ref s) => write!(f, "bad Size '{}'", s),
+            RecordParseError::BadRocks(ref s) => write!(f, "bad Rocks '{}'", s),
+            RecordParseError::BadPosition(ref e) => write!(f, "bad Position: {}", e),
+            RecordParseError::BadMove { line, ref token } =>
+                write!(f, "line {}: bad move '{}'", line, token),
+            RecordParseError::BadResult(ref s) => write!(f, "bad result '{}'", s),
+            RecordParseError::ConflictingResults =>
+                write!(f, "Result tag doesn't match the end of the moves"),
+        }
+    }
+}
+
+impl Error for RecordParseError {
+    fn description(&self) -> &str {
+        "invalid game record text"
+    }
+}
+
+// A game from its initial position (rocks included) to its last move. The text form is like
+// PGN:
+//
+//     [Blue "human"]
+//     [Red "pushfour-rust"]
+//     [Size "8x8"]
+//     [Rocks "1:3 4:4"]
+//     [Depth "7"]
+//     [Result "0-1"]
+//
+//     1. 0:0 7:7 2. 0:1 7:6 ... 0-1
+//
+// Moves are `row:col` and numbered in pairs, starting with whoever moves first in the initial
+// position. A Position tag (see Board::to_notation) replaces Size and Rocks when the game starts
+// from anything other than an empty board with rocks, Blue to move.
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub initial: Board,
+    pub blue: String,
+    pub red: String,
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+    // Any other metadata, such as engine settings or timestamps, in the order written
+    pub tags: Vec<(String, String)>,
+}
+
+impl GameRecord {
+    pub fn new(initial: Board) -> GameRecord {
+        GameRecord {
+            initial: initial,
+            blue: "?".to_string(),
+            red: "?".to_string(),
+            moves: Vec::new(),
+            result: GameResult::Unfinished,
+            tags: Vec::new(),
+        }
+    }
+
+    // Seconds since the epoch, for timestamp tags
+    pub fn timestamp() -> String {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs().to_string(),
+            Err(_) => "?".to_string(),
+        }
+    }
+
+    pub fn rocks(&self) -> Vec<(usize, usize)> {
+        let mut rocks = Vec::new();
+        for row in 0..self.initial.height() {
+            for col in 0..self.initial.width() {
+                if self.initial.get(row, col) == Some(Piece::Rock) { rocks.push((row, col)); }
+            }
+        }
+        rocks
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| &**v)
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        if let Some(t) = self.tags.iter_mut().find(|&&mut (ref n, _)| n == name) {
+            t.1 = value.to_string();
+            return;
+        }
+        self.tags.push((name.to_string(), value.to_string()));
+    }
+
+    // Plays the moves from the initial position, checking each against the legal moves, and
+    // returns the position before the first move and after every move. The recorded result
+    // must match the final position, unless it's Unfinished.
+    pub fn replay(&self) -> Result<Vec<Board>, ReplayError> {
+        let mut boards = vec![self.initial.clone()];
+        for (ply, &mv) in self.moves.iter().enumerate() {
+            let mut b = boards[ply].clone();
+            if GameResult::of(&b) != GameResult::Unfinished {
+                return Err(ReplayError::AfterGameOver { ply: ply, mv: mv });
+            }
+            if mv.player != b.turn() {
+                return Err(ReplayError::WrongPlayer { ply: ply, mv: mv });
+            }
+            if !b.get_moves_set().contains(&mv) {
+                return Err(ReplayError::IllegalMove { ply: ply, mv: mv });
+            }
+            b.make_move(mv);
+            boards.push(b);
+        }
+        let actual = GameResult::of(boards.last().unwrap());
+        if self.result != GameResult::Unfinished && self.result != actual {
+            return Err(ReplayError::ResultMismatch { recorded: self.result, actual: actual });
+        }
+        Ok(boards)
+    }
+
+    pub fn final_board(&self) -> Result<Board, ReplayError> {
+        self.replay().map(|mut boards| boards.pop().unwrap())
+    }
+
+    fn needs_position(&self) -> bool {
+        let b = &self.initial;
+        if b.turn() != Player::Blue || b.connect() != DEFAULT_CONNECT || b.plies() != 0 {
+            return true;
+        }
+        for row in 0..b.height() {
+            for col in 0..b.width() {
+                match b.get(row, col) {
+                    Some(Piece::Blue) | Some(Piece::Red) => return true,
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "[Blue \"{}\"]", escape(&self.blue)));
+        try!(writeln!(f, "[Red \"{}\"]", escape(&self.red)));
+        if self.needs_position() {
+            try!(writeln!(f, "[Position \"{}\"]", self.initial.to_notation()));
+        } else {
+            try!(writeln!(f, "[Size \"{}x{}\"]", self.initial.width(), self.initial.height()));
+            let rocks: Vec<String> = self.rocks().iter()
+                .map(|&(row, col)| format!("{}:{}", row, col)).collect();
+            try!(writeln!(f, "[Rocks \"{}\"]", rocks.join(" ")));
+        }
+        for &(ref name, ref value) in self.tags.iter() {
+            try!(writeln!(f, "[{} \"{}\"]", name, escape(value)));
+        }
+        try!(writeln!(f, "[Result \"{}\"]", self.result.token()));
+        try!(writeln!(f, ""));
+
+        let mut line = String::new();
+        for (ply, mv) in self.moves.iter().enumerate() {
+            let mut token = format!("{}:{}", mv.row, mv.col);
+            if ply % 2 == 0 { token = format!("{}. {}", ply / 2 + 1, token); }
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                try!(writeln!(f, "{}", line));
+                line.clear();
+            }
+            if !line.is_empty() { line.push(' '); }
+            line.push_str(&token);
+        }
+        if !line.is_empty() { line.push(' '); }
+        line.push_str(self.result.token());
+        writeln!(f, "{}", line)
+    }
+}
+
+// Parses `[Name "Value"]`
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    if !line.starts_with('[') || !line.ends_with(']') { return None; }
+    let inner = &line[1..line.len() - 1];
+    let space = match inner.find(' ') { Some(i) => i, None => return None };
+    let (name, quoted) = (&inner[..space], inner[space..].trim());
+    if name.is_empty() || quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+        return None;
+    }
+    let mut value = String::new();
+    let mut escaped = false;
+    for c in quoted[1..quoted.len() - 1].chars() {
+        if escaped || c != '\\' { value.push(c); }
+        escaped = !escaped && c == '\\';
+    }
+    Some((name.to_string(), value))
+}
+
+fn parse_coord(s: &str) -> Option<(usize, usize)> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() != 2 { return None; }
+    match (parts[0].parse::<usize>(), parts[1].parse::<usize>()) {
+        (Ok(row), Ok(col)) => Some((row, col)),
+        _ => None,
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordParseError;
+
+    // Parses the Display format. Moves are only checked for syntax; use replay() to check them
+    // against the board.
+    fn from_str(s: &str) -> Result<GameRecord, RecordParseError> {
+        let mut tags = Vec::new();
+        let mut movetext = Vec::new();
+        for (i, raw) in s.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() { continue; }
+            if line.starts_with('[') && movetext.is_empty() {
+                match parse_tag(line) {
+                    Some(t) => tags.push(t),
+                    None => return Err(RecordParseError::BadTag { line: i + 1 }),
+                }
+            } else {
+                movetext.push((i + 1, line));
+            }
+        }
+
+        let find = |name: &str| tags.iter().find(|&&(ref n, _)| n == name).map(|t| t.1.clone());
+        let initial = if let Some(p) = find("Position") {
+            try!(Board::from_notation(&p).map_err(RecordParseError::BadPosition))
+        } else if let Some(size) = find("Size") {
+            // Any field that isn't a number leaves no dimensions at all
+            let dims: Vec<usize> = size.split('x').map(|d| d.parse::<usize>().ok())
+                                       .collect::<Option<_>>().unwrap_or(Vec::new());
+            if dims.len() != 2 || dims[0] == 0 || dims[1] == 0 ||
+               dims[0] > MAX_BOARD_DIM || dims[1] > MAX_BOARD_DIM {
+                return Err(RecordParseError::BadSize(size));
+            }
+            let mut b = Board::new_rect(dims[0], dims[1]);
+            let rocks = find("Rocks").unwrap_or(String::new());
+            for r in rocks.split_whitespace() {
+                match parse_coord(r) {
+                    Some((row, col)) if row < dims[1] && col < dims[0] =>
+                        b.set(row, col, Some(Piece::Rock)),
+                    _ => return Err(RecordParseError::BadRocks(rocks.clone())),
+                }
+            }
+            b
+        } else {
+            return Err(RecordParseError::MissingBoard);
+        };
+
+        let mut record = GameRecord::new(initial);
+        let mut tag_result = None;
+        for (name, value) in tags.into_iter() {
+            match &*name {
+                "Blue" => record.blue = value,
+                "Red" => record.red = value,
+                "Position" | "Size" | "Rocks" => {}
+                "Result" => match GameResult::from_token(&value) {
+                    Some(r) => tag_result = Some(r),
+                    None => return Err(RecordParseError::BadResult(value)),
+                },
+                _ => record.tags.push((name, value)),
+            }
+        }
+
+        let mut player = record.initial.turn();
+        let mut text_result = None;
+        for (line, text) in movetext {
+            for token in text.split_whitespace() {
+                if text_result.is_some() {
+                    return Err(RecordParseError::BadMove { line: line, token: token.to_string() });
+                }
+                if let Some(r) = GameResult::from_token(token) {
+                    text_result = Some(r);
+                    continue;
+                }
+                if token.ends_with('.') && token[..token.len() - 1].parse::<usize>().is_ok() {
+                    continue;
+                }
+                match parse_coord(token) {
+                    Some((row, col)) => {
+                        record.moves.push(Move { row: row, col: col, player: player });
+                        player = player.other();
+                    }
+                    None => return Err(RecordParseError::BadMove {
+                        line: line,
+                        token: token.to_string(),
+                    }),
+                }
+            }
+        }
+        record.result = match (tag_result, text_result) {
+            (Some(a), Some(b)) if a != b => return Err(RecordParseError::ConflictingResults),
+            (Some(r), _) | (None, Some(r)) => r,
+            (None, None) => GameResult::Unfinished,
+        };
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+fn sample_record() -> GameRecord {
+    let mut b = Board::new(5);
+    b.set(1, 3, Some(Piece::Rock));
+    b.set(4, 4, Some(Piece::Rock));
+    let mut record = GameRecord::new(b);
+    record.blue = "human".to_string();
+    record.red = "pushfour \"rust\"".to_string();
+    record.set_tag("Depth", "7");
+    let moves = [(0, 0), (4, 3), (0, 1), (4, 2), (0, 2), (4, 1), (0, 3)];
+    for (i, &(row, col)) in moves.iter().enumerate() {
+        let player = if i % 2 == 0 { Player::Blue } else { Player::Red };
+        record.moves.push(Move { row: row, col: col, player: player });
+    }
+    record.result = GameResult::BlueWins;
+    record
+}
+
+#[test]
+fn test_record_text() {
+    let record = sample_record();
+    let text = record.to_string();
+    assert_eq!("[Blue \"human\"]
+[Red \"pushfour \\\"rust\\\"\"]
+[Size \"5x5\"]
+[Rocks \"1:3 4:4\"]
+[Depth \"7\"]
+[Result \"1-0\"]
+
+1. 0:0 4:3 2. 0:1 4:2 3. 0:2 4:1 4. 0:3 1-0
+", text);
+
+    let parsed = GameRecord::from_str(&text).unwrap();
+    assert_eq!(record.moves, parsed.moves);
+    assert_eq!((&record.blue, &record.red, record.result, &record.tags),
+               (&parsed.blue, &parsed.red, parsed.result, &parsed.tags));
+    assert_eq!(vec![(1, 3), (4, 4)], parsed.rocks());
+    assert_eq!(text, parsed.to_string());
+}
+
+#[test]
+fn test_record_position() {
+    let mut b = Board::from_notation("2b1/#3/4/r3 r 4x4 2").unwrap();
+    b.set_connect(3);
+    let mut record = GameRecord::new(b);
+    record.moves.push(Move { row: 3, col: 3, player: Player::Red });
+    let text = record.to_string();
+    assert!(text.contains("[Position \"2b1/#3/4/r3 r 4x4c3 2\"]"));
+    let parsed = GameRecord::from_str(&text).unwrap();
+    assert_eq!(record.initial.to_notation(), parsed.initial.to_notation());
+    assert_eq!(record.moves, parsed.moves);
+}
+
+#[test]
+fn test_replay() {
+    let record = sample_record();
+    let boards = record.replay().unwrap();
+    assert_eq!(8, boards.len());
+    assert!(boards[7].is_win_state(Player::Blue));
+    assert_eq!(Some(Piece::Red), boards[7].get(4, 2));
+}
+
+#[test]
+fn test_replay_errors() {
+    let mut record = sample_record();
+    record.moves[1].player = Player::Blue;
+    assert_eq!(Err(ReplayError::WrongPlayer { ply: 1, mv: record.moves[1] }),
+               record.replay().map(|_| ()));
+
+    let mut record = sample_record();
+    record.moves[2] = Move { row: 2, col: 2, player: Player::Blue };
+    assert_eq!(Err(ReplayError::IllegalMove { ply: 2, mv: record.moves[2] }),
+               record.replay().map(|_| ()));
+
+    let mut record = sample_record();
+    record.moves.push(Move { row: 3, col: 0, player: Player::Red });
+    assert_eq!(Err(ReplayError::AfterGameOver { ply: 7, mv: record.moves[7] }),
+               record.replay().map(|_| ()));
+
+    let mut record = sample_record();
+    record.result = GameResult::RedWins;
+    assert_eq!(Err(ReplayError::ResultMismatch { recorded: GameResult::RedWins,
+                                                 actual: GameResult::BlueWins }),
+               record.replay().map(|_| ()));
+}
+
+#[test]
+fn test_record_parse_errors() {
+    assert_eq!(Err(RecordParseError::MissingBoard),
+               GameRecord::from_str("[Blue \"x\"]\n\n*").map(|_| ()));
+    assert_eq!(Err(RecordParseError::BadTag { line: 1 }),
+               GameRecord::from_str("[Blue x]\n\n*").map(|_| ()));
+    for &size in ["4xfoox4", "4x4x", "x4x4", "4x", "4", "0x4", "4x65"].iter() {
+        assert_eq!(Err(RecordParseError::BadSize(size.to_string())),
+                   GameRecord::from_str(&format!("[Size \"{}\"]", size)).map(|_| ()));
+    }
+    assert_eq!(Err(RecordParseError::BadRocks("9:9".to_string())),
+               GameRecord::from_str("[Size \"4x4\"]\n[Rocks \"9:9\"]").map(|_| ()));
+    assert_eq!(Err(RecordParseError::BadMove { line: 3, token: "1-1".to_string() }),
+               GameRecord::from_str("[Size \"4x4\"]\n\n1. 0:0 1-1").map(|_| ()));
+    assert_eq!(Err(RecordParseError::ConflictingResults),
+               GameRecord::from_str("[Size \"4x4\"]\n[Result \"1-0\"]\n\n1. 0:0 0-1")
+                   .map(|_| ()));
+}
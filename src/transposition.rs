@@ -0,0 +1,127 @@
+// Fixed-size transposition table for Minimax, indexed by a hash of the game state (e.g. the
+// Board's Zobrist hash). Each slot holds one entry; on collision, an entry is only replaced by one
+// for the same position or one searched at least as deeply.
+
+// How a stored score relates to the position's true value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // The search failed high: the true value is at least `score`
+    Lower,
+    // The search failed low: the true value is at most `score`
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry<Move> {
+    pub key: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub stores: u64,
+    // Probes whose entry was deep and tight enough to end the search of a node outright
+    pub cutoffs: u64,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        let probes = self.hits + self.misses;
+        if probes == 0 { 0.0 } else { self.hits as f64 / probes as f64 }
+    }
+}
+
+pub struct TranspositionTable<Move> {
+    entries: Vec<Option<Entry<Move>>>,
+    mask: usize,
+    stats: TableStats,
+}
+
+impl<Move: Copy> TranspositionTable<Move> {
+    // Creates a table with room for `size` entries, rounded up to a power of two
+    pub fn new(size: usize) -> TranspositionTable<Move> {
+        let size = size.next_power_of_two();
+        TranspositionTable {
+            entries: vec![None; size],
+            mask: size - 1,
+            stats: TableStats::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    pub fn clear(&mut self) {
+        for e in self.entries.iter_mut() { *e = None; }
+        self.stats = TableStats::default();
+    }
+
+    // Looks up the entry for `key`, counting the lookup as a hit or a miss
+    pub fn probe(&mut self, key: u64) -> Option<Entry<Move>> {
+        match self.entries[key as usize & self.mask] {
+            Some(e) if e.key == key => {
+                self.stats.hits += 1;
+                Some(e)
+            }
+            _ => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn store(&mut self, key: u64, depth: i32, score: i32, bound: Bound,
+                 best_move: Option<Move>) {
+        let slot = &mut self.entries[key as usize & self.mask];
+        if let Some(ref e) = *slot {
+            if e.key != key && e.depth > depth { return; }
+        }
+        *slot = Some(Entry {
+            key: key,
+            depth: depth,
+            score: score,
+            bound: bound,
+            best_move: best_move,
+        });
+        self.stats.stores += 1;
+    }
+
+    pub fn record_cutoff(&mut self) {
+        self.stats.cutoffs += 1;
+    }
+}
+
+#[test]
+fn test_probe_store() {
+    let mut tt: TranspositionTable<u8> = TranspositionTable::new(10);
+    assert_eq!(16, tt.capacity());
+    assert!(tt.probe(42).is_none());
+    tt.store(42, 3, 7, Bound::Exact, Some(1));
+    let e = tt.probe(42).unwrap();
+    assert_eq!((3, 7, Bound::Exact, Some(1)), (e.depth, e.score, e.bound, e.best_move));
+    assert_eq!(TableStats { hits: 1, misses: 1, stores: 1, cutoffs: 0 }, tt.stats());
+}
+
+#[test]
+fn test_replacement() {
+    let mut tt: TranspositionTable<u8> = TranspositionTable::new(16);
+    tt.store(1, 5, 0, Bound::Exact, None);
+    // Same slot, different position, shallower: keep the deeper entry
+    tt.store(17, 2, 0, Bound::Exact, None);
+    assert!(tt.probe(1).is_some());
+    assert!(tt.probe(17).is_none());
+    // Same position is always refreshed
+    tt.store(1, 1, 9, Bound::Lower, None);
+    assert_eq!(9, tt.probe(1).unwrap().score);
+}
@@ -8,7 +8,12 @@ use std::collections::HashSet;
 
 pub const BOARD_SIZE: usize = 8;
 
-pub const BOARD_DIAG_SIZE: usize = BOARD_SIZE * 2 - 1;
+// Boards can be at most this many rows or columns; every row, column and diagonal must fit in a
+// u64 with a bit to spare.
+pub const MAX_BOARD_DIM: usize = 63;
+
+// Number of pieces in a row needed to win, unless a board says otherwise
+pub const DEFAULT_CONNECT: usize = 4;
 pub const BLUE_CHAR: char = 'b';
 pub const RED_CHAR: char = 'r';
 pub const ROCK_CHAR: char = '#';
@@ -125,6 +130,13 @@ impl Player {
             Player::Blue => Piece::Blue,
         }
     }
+
+    pub fn other(&self) -> Player {
+        match *self {
+            Player::Red => Player::Blue,
+            Player::Blue => Player::Red,
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
@@ -0,0 +1,42 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+use util::*;
+
+// Random keys for Zobrist hashing: one per (row, col, piece), and one that is mixed in while it's
+// Red's turn. A Board's hash is the XOR of the keys of everything on it, so it can be updated
+// incrementally as pieces come and go. The seed is fixed so hashes are the same between runs.
+struct Keys {
+    cells: Vec<u64>,
+    red_turn: u64,
+}
+
+impl Keys {
+    fn new() -> Keys {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([0x2f6b_1e4d, 0x9a35_c0e1, 0x51d2_7b08,
+                                                           0xe8c4_36a7]);
+        Keys {
+            cells: (0..MAX_BOARD_DIM * MAX_BOARD_DIM * 3).map(|_| rng.next_u64()).collect(),
+            red_turn: rng.next_u64(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref KEYS: Keys = Keys::new();
+}
+
+#[inline(always)]
+fn piece_idx(piece: &Piece) -> usize {
+    match *piece {
+        Piece::Blue => 0,
+        Piece::Red => 1,
+        Piece::Rock => 2,
+    }
+}
+
+pub fn piece_key(row: usize, col: usize, piece: &Piece) -> u64 {
+    (*KEYS).cells[(row * MAX_BOARD_DIM + col) * 3 + piece_idx(piece)]
+}
+
+pub fn red_turn_key() -> u64 {
+    (*KEYS).red_turn
+}